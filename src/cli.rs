@@ -0,0 +1,1267 @@
+use crate::err::TodoError;
+use chrono::Utc;
+use std::env;
+use crate::todo::{
+    self, BackendKind, Column, ItemChanges, Priority, Query, Recurrence, SavedView, SortOrder,
+    Timestamp, TodoItem, TodoStore, UndatedPosition,
+};
+
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+// Environment variable used to pick a storage backend when --backend is not given
+pub const BACKEND_ENV_VAR: &str = "TODO_ASSISTANT_BACKEND";
+// Environment variable used to limit title length when --max-title-length is not given
+pub const MAX_TITLE_LENGTH_ENV_VAR: &str = "TODO_ASSISTANT_MAX_TITLE_LENGTH";
+
+pub const USAGE: &str = "\
+Usage: todo_assistant [OPTIONS] [COMMAND]
+
+Run without a command to start the interactive menu.
+
+Options:
+  --backend {json|sqlite}   Storage backend (defaults to $TODO_ASSISTANT_BACKEND, then json)
+  --store {PATH}            Store file (defaults to todo_store_data.json or .sqlite)
+  --max-title-length {N}    Longest title allowed for new and edited items, in characters
+                            (defaults to $TODO_ASSISTANT_MAX_TITLE_LENGTH, then 50)
+
+Commands:
+  add [{Due date} [Due time]] {Title} [--priority {high|medium|low}] [--tag {Tag}]...
+      [--project {Project}] [--parent {ID}] [--blocked-by {ID}]... [--repeat {Rule}]
+                            Create a new item, with no due date if the input does not start with
+                            one. Words like !high, #ops, @work, ^3 and *weekly in the title also
+                            set the priority, add tags, pick an existing project, the parent item
+                            and the repeat rule. Other words, and words starting with a backslash
+                            as in \\#ops, stay in the title.
+  done {ID} [--force]       Mark an item as complete. --force also completes its open subtasks
+                            and ignores open blockers. Completing a repeating item creates its
+                            next occurrence.
+  reopen {ID}               Mark a completed item as incomplete again
+  edit {ID} [--due {Due date} [Due time]|none] [--title {Title}] [--priority {high|medium|low|none}]
+       [--tags {Tag}...|none] [--parent {ID}|none] [--blocked-by {ID}...|none]
+       [--repeat {Rule}|none]
+                            Change the due date, title, priority, tags, parent, blockers and/or
+                            repeat rule of an item
+  move {ID} {Project|none}  Move an item into a project, or out of its project
+  delete {ID}               Move an item to the trash
+  restore {ID}              Move an item out of the trash
+  list [--incomplete|--ready|--overdue|--all] [--tag {Tag}] [--project {Project}]
+       [--sort {due|priority}] [--undated {first|last}] [--tree]
+       [--timestamps {created,completed,updated}] [{Query}]
+                            List items (defaults to --all, sorted by due date with undated items
+                            last). --ready shows incomplete items that are not blocked and
+                            --overdue incomplete items past their due date and time. --tree
+                            indents subtasks below their parents. --timestamps adds columns for
+                            when items were created, completed or last changed. A query
+                            narrows down and sorts the list, see below.
+  find {Text}               List items whose titles contain the letters of each word of the text
+                            in order, ignoring case, best match first. Matches are highlighted
+                            when printing to a terminal unless $NO_COLOR is set.
+  agenda                    List incomplete items grouped into overdue, today, tomorrow, the rest
+                            of this week, later and no due date
+  projects                  List projects
+  project add {Project}     Create a project
+  project rename {Project} {New name}
+                            Rename a project
+  project archive {Project} Hide a project and its items from the regular lists
+  project unarchive {Project}
+                            Bring an archived project back
+  views                     List saved views
+  view {Name}               List the items matching a saved view
+  view save {Name} [--columns {Column},...] {Query}
+                            Save a query as a view, replacing any view with that name. Columns
+                            are id, done, due, priority, title, repeats, blocked-by, tags,
+                            project, created, completed and updated.
+  view delete {Name}        Delete a saved view
+  history                   List completed items, most recently completed first
+  trash                     List items in the trash
+  empty-trash               Permanently remove every item in the trash
+  undo                      Revert the most recent change
+  redo                      Re-apply the most recently undone change
+  help                      Show this message
+
+Due dates:
+  YYYY-MM-DD, today, tomorrow, a weekday like fri or on fri (the next one after today), next
+  monday (in the following week), +3d, +2w, +1m, in 2 weeks or eom (the end of this month). When
+  adding an item a weekday on its own is part of the title, as in \"Friday drinks\", unless a due
+  time follows it, so write \"on fri Deploy\" or \"fri 3pm Deploy\".
+
+Due times:
+  HH:MM or 3pm, optionally followed by UTC or an offset like +02:00. Without a time an item is
+  due by the end of its due date, and times without an offset are in the local time zone.
+
+Repeat rules:
+  daily, weekly, weekly:mon,thu, monthly, monthly:15 or after:3d (3 days after completion)
+
+Queries:
+  Terms separated by spaces, all of which have to match. A leading - negates a term.
+    status:{open|done|ready|blocked|overdue}   due{<|<=|:|>=|>}{Due date}   due:none
+    tag:{Tag}   project:{Project|none}   priority:{high|medium|low|none}   title~{Text}
+    sort:[-]{id|due|priority|title|created|completed|updated}[,...]
+  Other words are searched for in titles. Quote text with spaces, as in title~\"deploy to prod\".
+  On the command line each argument is one term, so 'title~deploy to prod' works as well.
+  Example: status:open 'due<2023-02-01' tag:ops title~deploy sort:-due";
+
+#[derive(Debug, Default, PartialEq)]
+pub enum ListFilter {
+    #[default]
+    All,
+    Incomplete,
+    // Incomplete and not blocked
+    Ready,
+    // Incomplete and past the deadline
+    Overdue,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ListOptions {
+    pub filter: ListFilter,
+    pub tag: Option<String>,
+    pub project: Option<String>,
+    pub tree: bool,
+    // None keeps the default order
+    pub sort: Option<String>,
+    // None keeps undated items last
+    pub undated: Option<String>,
+    // Comma separated created, completed and updated columns to add
+    pub timestamps: Option<String>,
+    // Filter and sort expression such as "status:open tag:ops sort:-due"
+    pub query: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ProjectCommand {
+    Add { name: String },
+    Rename { name: String, new_name: String },
+    Archive { name: String },
+    Unarchive { name: String },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ViewCommand {
+    Show { name: String },
+    Save {
+        name: String,
+        query: String,
+        // Comma separated columns, None for the regular ones
+        columns: Option<String>,
+    },
+    Delete { name: String },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Add {
+        // Due date, due time and title as typed, split the same way as in the interactive menu
+        input: String,
+        priority: Option<String>,
+        tags: Vec<String>,
+        project: Option<String>,
+        parent: Option<String>,
+        blocked_by: Vec<String>,
+        repeat: Option<String>,
+    },
+    Done {
+        id: usize,
+        force: bool,
+    },
+    Reopen { id: usize },
+    Edit {
+        id: usize,
+        due_date: Option<String>,
+        title: Option<String>,
+        priority: Option<String>,
+        tags: Option<String>,
+        parent: Option<String>,
+        blocked_by: Option<String>,
+        repeat: Option<String>,
+    },
+    Move {
+        id: usize,
+        // None takes the item out of its project
+        project: Option<String>,
+    },
+    Delete { id: usize },
+    Restore { id: usize },
+    List(ListOptions),
+    // Fuzzy search of titles, best match first
+    Find { text: String },
+    Agenda,
+    Projects,
+    Project(ProjectCommand),
+    Views,
+    View(ViewCommand),
+    History,
+    Trash,
+    EmptyTrash,
+    Undo,
+    Redo,
+    Help,
+}
+
+// Defaults taken from the environment, overridden by the matching global options
+#[derive(Debug, Default)]
+pub struct Configuration {
+    pub backend: Option<String>,
+    pub max_title_length: Option<String>,
+}
+
+impl Configuration {
+    pub fn from_env() -> Configuration {
+        Configuration {
+            backend: env::var(BACKEND_ENV_VAR).ok(),
+            max_title_length: env::var(MAX_TITLE_LENGTH_ENV_VAR).ok(),
+        }
+    }
+}
+
+// Everything needed to run the program once: where the store lives and what to do with it
+#[derive(Debug, PartialEq)]
+pub struct Invocation {
+    pub backend: BackendKind,
+    pub store_filepath: String,
+    // None keeps the default limit
+    pub max_title_length: Option<usize>,
+    // None starts the interactive menu
+    pub command: Option<Command>,
+}
+
+impl Invocation {
+    pub fn parse(args: &[String], configuration: &Configuration) -> Result<Invocation, TodoError> {
+        let mut backend = configuration
+            .backend
+            .as_deref()
+            .map(BackendKind::parse)
+            .transpose()?;
+        let mut max_title_length = configuration
+            .max_title_length
+            .as_deref()
+            .map(parse_max_title_length)
+            .transpose()?;
+        let mut store_filepath = None;
+
+        // Global options come before the command
+        let mut args = args;
+        while let Some((option, rest)) = args.split_first() {
+            match option.as_str() {
+                "--backend" => {
+                    let (value, rest) = rest.split_first().ok_or(TodoError::new_from_msg(
+                        String::from("--backend requires a value."),
+                    ))?;
+                    backend = Some(BackendKind::parse(value)?);
+                    args = rest;
+                }
+                "--store" => {
+                    let (value, rest) = rest.split_first().ok_or(TodoError::new_from_msg(
+                        String::from("--store requires a path."),
+                    ))?;
+                    store_filepath = Some(value.clone());
+                    args = rest;
+                }
+                "--max-title-length" => {
+                    let (value, rest) = rest.split_first().ok_or(TodoError::new_from_msg(
+                        String::from("--max-title-length requires a value."),
+                    ))?;
+                    max_title_length = Some(parse_max_title_length(value)?);
+                    args = rest;
+                }
+                _ => break,
+            }
+        }
+
+        let backend = backend.unwrap_or(BackendKind::Json);
+        let command = if args.is_empty() {
+            None
+        } else {
+            Some(Command::parse(args)?)
+        };
+
+        Ok(Invocation {
+            backend,
+            store_filepath: store_filepath
+                .unwrap_or_else(|| String::from(backend.default_filepath())),
+            max_title_length,
+            command,
+        })
+    }
+
+    pub fn open_store(&self) -> Result<TodoStore, TodoError> {
+        let mut store = TodoStore::new_from_backend(self.backend.open(&self.store_filepath)?)?;
+        if let Some(max_title_length) = self.max_title_length {
+            store.set_max_title_length(max_title_length);
+        }
+
+        Ok(store)
+    }
+}
+
+fn parse_max_title_length(input: &str) -> Result<usize, TodoError> {
+    match input.trim().parse::<usize>() {
+        Ok(length) if length > 0 => Ok(length),
+        _ => Err(TodoError::new_from_msg(format!(
+            "Invalid max title length '{}'. Expected a positive number.",
+            input
+        ))),
+    }
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Command, TodoError> {
+        let (command, args) = args
+            .split_first()
+            .ok_or(TodoError::new_from_msg(String::from("A command must be provided.")))?;
+
+        match command.as_str() {
+            "add" => {
+                // Options may appear anywhere after the due date and take a single value
+                let mut priority = None;
+                let mut tags = Vec::new();
+                let mut project = None;
+                let mut parent = None;
+                let mut blocked_by = Vec::new();
+                let mut repeat = None;
+                let mut words = Vec::new();
+                let mut args = args.iter();
+                while let Some(arg) = args.next() {
+                    match arg.as_str() {
+                        "--priority" | "--tag" | "--project" | "--parent" | "--blocked-by"
+                        | "--repeat" => {
+                            let value = args.next().ok_or(TodoError::new_from_msg(format!(
+                                "{} requires a value.",
+                                arg
+                            )))?;
+                            match arg.as_str() {
+                                "--priority" => priority = Some(value.clone()),
+                                "--tag" => tags.push(value.clone()),
+                                "--project" => project = Some(value.clone()),
+                                "--parent" => parent = Some(value.clone()),
+                                "--repeat" => repeat = Some(value.clone()),
+                                _ => blocked_by.push(value.clone()),
+                            }
+                        }
+                        _ => words.push(arg.clone()),
+                    }
+                }
+
+                if words.is_empty() {
+                    return Err(TodoError::new_from_msg(String::from("'add' requires a title.")));
+                }
+
+                Ok(Command::Add {
+                    input: words.join(" "),
+                    priority,
+                    tags,
+                    project,
+                    parent,
+                    blocked_by,
+                    repeat,
+                })
+            }
+            "done" => {
+                let force = args.iter().any(|arg| arg == "--force");
+                let args: Vec<String> = args
+                    .iter()
+                    .filter(|arg| *arg != "--force")
+                    .cloned()
+                    .collect();
+
+                Ok(Command::Done {
+                    id: parse_single_id("done", &args)?,
+                    force,
+                })
+            }
+            "reopen" => Ok(Command::Reopen {
+                id: parse_single_id("reopen", args)?,
+            }),
+            "move" => match args {
+                [id, project] => Ok(Command::Move {
+                    id: parse_id("move", id)?,
+                    project: match project.as_str() {
+                        "none" => None,
+                        project => Some(String::from(project)),
+                    },
+                }),
+                _ => Err(TodoError::new_from_msg(String::from(
+                    "'move' requires an ID and a project name or none.",
+                ))),
+            },
+            "delete" => Ok(Command::Delete {
+                id: parse_single_id("delete", args)?,
+            }),
+            "restore" => Ok(Command::Restore {
+                id: parse_single_id("restore", args)?,
+            }),
+            "edit" => {
+                let (id, mut options) = args.split_first().ok_or(TodoError::new_from_msg(
+                    String::from("'edit' requires an ID."),
+                ))?;
+                let id = parse_id("edit", id)?;
+
+                let mut due_date = None;
+                let mut title = None;
+                let mut priority = None;
+                let mut tags = None;
+                let mut parent = None;
+                let mut blocked_by = None;
+                let mut repeat = None;
+                while let Some((option, rest)) = options.split_first() {
+                    // An option's value runs until the next option so titles need no quoting
+                    let value_length = rest
+                        .iter()
+                        .position(|arg| arg.starts_with("--"))
+                        .unwrap_or(rest.len());
+                    let (value, rest) = rest.split_at(value_length);
+                    if value.is_empty() {
+                        return Err(TodoError::new_from_msg(format!("{} requires a value.", option)));
+                    }
+
+                    match option.as_str() {
+                        "--due" => due_date = Some(value.join(" ")),
+                        "--title" => title = Some(value.join(" ")),
+                        "--priority" => priority = Some(value.join(" ")),
+                        "--tags" => tags = Some(value.join(" ")),
+                        "--parent" => parent = Some(value.join(" ")),
+                        "--blocked-by" => blocked_by = Some(value.join(" ")),
+                        "--repeat" => repeat = Some(value.join(" ")),
+                        other => {
+                            return Err(TodoError::new_from_msg(format!(
+                                "Unknown option '{}' for 'edit'.",
+                                other
+                            )))
+                        }
+                    }
+                    options = rest;
+                }
+                if [&due_date, &title, &priority, &tags, &parent, &blocked_by, &repeat]
+                    .iter()
+                    .all(|value| value.is_none())
+                {
+                    return Err(TodoError::new_from_msg(String::from(
+                        "'edit' requires at least one of --due, --title, --priority, --tags, --parent, --blocked-by or --repeat.",
+                    )));
+                }
+
+                Ok(Command::Edit {
+                    id,
+                    due_date,
+                    title,
+                    priority,
+                    tags,
+                    parent,
+                    blocked_by,
+                    repeat,
+                })
+            }
+            "list" => {
+                let mut options = ListOptions::default();
+                let mut args = args.iter();
+                while let Some(arg) = args.next() {
+                    match arg.as_str() {
+                        "--all" => options.filter = ListFilter::All,
+                        "--incomplete" => options.filter = ListFilter::Incomplete,
+                        "--ready" => options.filter = ListFilter::Ready,
+                        "--overdue" => options.filter = ListFilter::Overdue,
+                        "--tree" => options.tree = true,
+                        "--sort" | "--undated" | "--timestamps" | "--tag" | "--project" => {
+                            let value = args.next().ok_or(TodoError::new_from_msg(format!(
+                                "{} requires a value.",
+                                arg
+                            )))?;
+                            match arg.as_str() {
+                                "--sort" => options.sort = Some(value.clone()),
+                                "--undated" => options.undated = Some(value.clone()),
+                                "--timestamps" => options.timestamps = Some(value.clone()),
+                                "--tag" => options.tag = Some(value.clone()),
+                                _ => options.project = Some(value.clone()),
+                            }
+                        }
+                        other if other.starts_with("--") => {
+                            return Err(TodoError::new_from_msg(format!(
+                                "Unknown option '{}' for 'list'.",
+                                other
+                            )))
+                        }
+                        _ => {
+                            let term = query_term(arg);
+                            options.query = Some(match options.query {
+                                Some(query) => format!("{} {}", query, term),
+                                None => term,
+                            })
+                        }
+                    }
+                }
+
+                Ok(Command::List(options))
+            }
+            "find" => {
+                let text = args.join(" ");
+                if text.trim().is_empty() {
+                    return Err(TodoError::new_from_msg(String::from(
+                        "'find' requires some text to search for.",
+                    )));
+                }
+
+                Ok(Command::Find {
+                    text: String::from(text.trim()),
+                })
+            }
+            "agenda" => Ok(Command::Agenda),
+            "projects" => Ok(Command::Projects),
+            "project" => Ok(Command::Project(ProjectCommand::parse(args)?)),
+            "views" => Ok(Command::Views),
+            "view" => Ok(Command::View(ViewCommand::parse(args)?)),
+            "history" => Ok(Command::History),
+            "trash" => Ok(Command::Trash),
+            "empty-trash" => Ok(Command::EmptyTrash),
+            "undo" => Ok(Command::Undo),
+            "redo" => Ok(Command::Redo),
+            "help" | "--help" | "-h" => Ok(Command::Help),
+            other => Err(TodoError::new_from_msg(format!("Unknown command '{}'.", other))),
+        }
+    }
+}
+
+impl ProjectCommand {
+    pub fn parse(args: &[String]) -> Result<ProjectCommand, TodoError> {
+        let (subcommand, args) = args.split_first().ok_or(TodoError::new_from_msg(String::from(
+            "'project' requires add, rename, archive or unarchive.",
+        )))?;
+
+        let command = match (subcommand.as_str(), args) {
+            ("add", [name]) => ProjectCommand::Add { name: name.clone() },
+            ("rename", [name, new_name]) => ProjectCommand::Rename {
+                name: name.clone(),
+                new_name: new_name.clone(),
+            },
+            ("archive", [name]) => ProjectCommand::Archive { name: name.clone() },
+            ("unarchive", [name]) => ProjectCommand::Unarchive { name: name.clone() },
+            ("add" | "archive" | "unarchive", _) => {
+                return Err(TodoError::new_from_msg(format!(
+                    "'project {}' requires exactly one project name.",
+                    subcommand
+                )))
+            }
+            ("rename", _) => {
+                return Err(TodoError::new_from_msg(String::from(
+                    "'project rename' requires the current and the new project name.",
+                )))
+            }
+            (other, _) => {
+                return Err(TodoError::new_from_msg(format!(
+                    "Unknown project command '{}'. Expected add, rename, archive or unarchive.",
+                    other
+                )))
+            }
+        };
+
+        Ok(command)
+    }
+}
+
+impl ViewCommand {
+    pub fn parse(args: &[String]) -> Result<ViewCommand, TodoError> {
+        let command = match args {
+            [subcommand, name, rest @ ..] if subcommand == "save" => {
+                let mut query = Vec::new();
+                let mut columns = None;
+                let mut rest = rest.iter();
+                while let Some(arg) = rest.next() {
+                    match arg.as_str() {
+                        "--columns" => {
+                            columns = Some(
+                                rest.next()
+                                    .ok_or(TodoError::new_from_msg(String::from(
+                                        "--columns requires a value.",
+                                    )))?
+                                    .clone(),
+                            )
+                        }
+                        _ => query.push(query_term(arg)),
+                    }
+                }
+                if query.is_empty() {
+                    return Err(TodoError::new_from_msg(String::from(
+                        "'view save' requires a view name and a query.",
+                    )));
+                }
+
+                ViewCommand::Save {
+                    name: name.clone(),
+                    query: query.join(" "),
+                    columns,
+                }
+            }
+            [subcommand, name] if subcommand == "delete" => ViewCommand::Delete { name: name.clone() },
+            [subcommand, ..] if subcommand == "save" => {
+                return Err(TodoError::new_from_msg(String::from(
+                    "'view save' requires a view name and a query.",
+                )))
+            }
+            [subcommand, ..] if subcommand == "delete" => {
+                return Err(TodoError::new_from_msg(String::from(
+                    "'view delete' requires exactly one view name.",
+                )))
+            }
+            [name] => ViewCommand::Show { name: name.clone() },
+            _ => {
+                return Err(TodoError::new_from_msg(String::from(
+                    "'view' requires a view name, or save or delete.",
+                )))
+            }
+        };
+
+        Ok(command)
+    }
+}
+
+// Each argument is one query term, so one that the shell kept together, such as
+// title~"deploy to prod", is quoted again before the terms are joined. Arguments that already
+// contain quotes are left as they are.
+fn query_term(arg: &str) -> String {
+    if !arg.contains(char::is_whitespace) || arg.contains('"') {
+        return String::from(arg);
+    }
+
+    let value_start = arg
+        .find(|c: char| "<>=:~".contains(c))
+        .and_then(|start| arg[start..].find(|c: char| !"<>=:~".contains(c)).map(|end| start + end))
+        .unwrap_or(0);
+    format!("{}\"{}\"", &arg[..value_start], &arg[value_start..])
+}
+
+fn parse_single_id(command: &str, args: &[String]) -> Result<usize, TodoError> {
+    match args {
+        [id] => parse_id(command, id),
+        _ => Err(TodoError::new_from_msg(format!(
+            "'{}' requires exactly one ID.",
+            command
+        ))),
+    }
+}
+
+fn parse_id(command: &str, id: &str) -> Result<usize, TodoError> {
+    id.parse::<usize>().map_err(|err| {
+        TodoError::new(format!("'{}' requires a valid ID.", command), Box::new(err))
+    })
+}
+
+pub fn execute(command: Command, store: &mut TodoStore) -> Result<(), TodoError> {
+    match command {
+        Command::Add {
+            input,
+            priority,
+            tags,
+            project,
+            parent,
+            blocked_by,
+            repeat,
+        } => {
+            // Same rules as the interactive menu, so !high and #tag markers work here too
+            let mut input = store.parse_input(&input)?;
+            if let Some(priority) = priority {
+                input.priority = Some(Priority::parse(&priority)?);
+            }
+            for tag in tags {
+                input.tags.insert(TodoItem::parse_tag(&tag)?);
+            }
+            if project.is_some() {
+                input.project = project;
+            }
+            if let Some(parent) = parent {
+                input.parent = Some(TodoItem::parse_parent_id(&parent)?);
+            }
+            input.blocked_by = TodoItem::parse_blockers(&blocked_by.join(" "))?;
+            if let Some(repeat) = repeat {
+                input.recurrence = Some(Recurrence::parse(&repeat)?);
+            }
+            store.create(input)?;
+        }
+        Command::Done { id, force: false } => {
+            store.complete(id)?;
+        }
+        Command::Done { id, force: true } => {
+            store.force_complete(id)?;
+        }
+        Command::Reopen { id } => {
+            store.reopen(id)?;
+        }
+        Command::Edit {
+            id,
+            due_date,
+            title,
+            priority,
+            tags,
+            parent,
+            blocked_by,
+            repeat,
+        } => {
+            // A new due date replaces the due time as well
+            let due = due_date
+                .map(|due_date| TodoItem::parse_due(&due_date))
+                .transpose()?;
+            let changes = ItemChanges {
+                title,
+                due_date: due.map(|(due_date, _)| due_date),
+                due_time: due.map(|(_, due_time)| due_time),
+                priority: priority
+                    .map(|priority| Priority::parse_optional(&priority))
+                    .transpose()?,
+                tags: tags.map(|tags| TodoItem::parse_tags(&tags)).transpose()?,
+                project: None,
+                parent: parent
+                    .map(|parent| match parent.as_str() {
+                        "none" => Ok(None),
+                        parent => TodoItem::parse_parent_id(parent).map(Some),
+                    })
+                    .transpose()?,
+                blocked_by: blocked_by
+                    .map(|blocked_by| TodoItem::parse_blockers(&blocked_by))
+                    .transpose()?,
+                recurrence: repeat
+                    .map(|repeat| Recurrence::parse_optional(&repeat))
+                    .transpose()?,
+            };
+            store.edit(id, changes)?;
+        }
+        Command::Move { id, project } => {
+            store.move_item(id, project.as_deref())?;
+        }
+        Command::Delete { id } => {
+            store.delete(id)?;
+        }
+        Command::Restore { id } => {
+            store.restore(id)?;
+        }
+        Command::List(options) => {
+            if let Some(sort) = options.sort {
+                store.sort_by(SortOrder::parse(&sort)?);
+            }
+            if let Some(undated) = options.undated {
+                store.place_undated(UndatedPosition::parse(&undated)?);
+            }
+            if let Some(timestamps) = options.timestamps {
+                store.show_timestamps(Timestamp::parse_list(&timestamps)?);
+            }
+            let (mut data_title, mut items) = match (options.project, options.filter) {
+                (Some(project), filter) => {
+                    let name = store.get_project(&project)?.to_string();
+                    let mut items = store.list_project(&project)?;
+                    match filter {
+                        ListFilter::All => {}
+                        ListFilter::Incomplete => items.retain(|item| !item.complete),
+                        ListFilter::Ready => {
+                            items.retain(|item| !item.complete && !store.is_blocked(item))
+                        }
+                        ListFilter::Overdue => {
+                            let now = Utc::now();
+                            items.retain(|item| item.is_overdue(now))
+                        }
+                    }
+                    (format!("Project: {}", name), items)
+                }
+                (None, ListFilter::All) => (String::from("All items"), store.list_all_todos()),
+                (None, ListFilter::Incomplete) => {
+                    (String::from("Incomplete items"), store.list_incomplete_todos())
+                }
+                (None, ListFilter::Ready) => (String::from("Ready items"), store.list_ready()),
+                (None, ListFilter::Overdue) => {
+                    (String::from("Overdue items"), store.list_overdue(Utc::now()))
+                }
+            };
+            if let Some(tag) = options.tag {
+                let tag = TodoItem::parse_tag(&tag)?;
+                items.retain(|item| item.has_tag(&tag));
+                data_title = format!("{} tagged #{}", data_title, tag);
+            }
+            if let Some(query) = options.query {
+                let parsed = Query::parse(&query)?;
+                let now = Utc::now();
+                items.retain(|item| parsed.matches(item, store, now));
+                parsed.sort(&mut items);
+                data_title = format!("{} matching {}", data_title, query);
+            }
+            match options.tree {
+                true => todo::print_tree(store, &data_title, &items),
+                false => todo::print_store(store, &data_title, &items),
+            }
+        }
+        Command::Find { text } => {
+            let matches = store.find(&text);
+            match matches.is_empty() {
+                true => println!("No items match '{}'.", text),
+                false => todo::print_matches(store, &format!("Items matching '{}'", text), &matches),
+            }
+        }
+        Command::Agenda => {
+            todo::print_agenda(store);
+        }
+        Command::Projects => {
+            todo::print_projects(store);
+        }
+        Command::Project(ProjectCommand::Add { name }) => {
+            store.create_project(&name)?;
+        }
+        Command::Project(ProjectCommand::Rename { name, new_name }) => {
+            store.rename_project(&name, &new_name)?;
+        }
+        Command::Project(ProjectCommand::Archive { name }) => {
+            store.archive_project(&name)?;
+        }
+        Command::Project(ProjectCommand::Unarchive { name }) => {
+            store.unarchive_project(&name)?;
+        }
+        Command::Views => {
+            todo::print_views(store);
+        }
+        Command::View(ViewCommand::Show { name }) => {
+            let view = store.get_view(&name)?.clone();
+            todo::print_view(store, &view)?;
+        }
+        Command::View(ViewCommand::Save {
+            name,
+            query,
+            columns,
+        }) => {
+            let columns = columns
+                .map(|columns| Column::parse_list(&columns))
+                .transpose()?
+                .unwrap_or_default();
+            store.save_view(SavedView::new(&name, &query, columns)?)?;
+        }
+        Command::View(ViewCommand::Delete { name }) => {
+            store.delete_view(&name)?;
+        }
+        Command::History => {
+            todo::print_history(store);
+        }
+        Command::Trash => {
+            todo::print_store(store, "Trash", &store.list_trash());
+        }
+        Command::EmptyTrash => {
+            let removed_item_count = store.empty_trash()?;
+            println!("Permanently removed {} item(s) from the trash.", removed_item_count);
+        }
+        Command::Undo => {
+            println!("Undid {}.", store.undo()?);
+        }
+        Command::Redo => {
+            println!("Redid {}.", store.redo()?);
+        }
+        Command::Help => {
+            println!("{}", USAGE);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod command_parse_tests {
+    use super::*;
+
+    fn args(input: &str) -> Vec<String> {
+        input.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn add_greenpath() {
+        let command = Command::parse(&args("add 2023-01-01 First Todo")).unwrap();
+
+        assert_eq!(
+            Command::Add {
+                input: String::from("2023-01-01 First Todo"),
+                priority: None,
+                tags: Vec::new(),
+                project: None,
+                parent: None,
+                blocked_by: Vec::new(),
+                repeat: None,
+            },
+            command
+        );
+    }
+
+    #[test]
+    fn add_with_options() {
+        let command =
+            Command::parse(&args("add 2023-01-01 First Todo --priority high --tag ops --tag web"))
+                .unwrap();
+
+        assert_eq!(
+            Command::Add {
+                input: String::from("2023-01-01 First Todo"),
+                priority: Some(String::from("high")),
+                tags: vec![String::from("ops"), String::from("web")],
+                project: None,
+                parent: None,
+                blocked_by: Vec::new(),
+                repeat: None,
+            },
+            command
+        );
+    }
+
+    #[test]
+    fn add_missing_title() {
+        let error = Command::parse(&args("add --priority high")).err().unwrap();
+
+        assert_eq!("'add' requires a title.", &error.message);
+    }
+
+    #[test]
+    fn add_without_due_date() {
+        let command = Command::parse(&args("add Learn Rust")).unwrap();
+
+        assert_eq!(
+            Command::Add {
+                input: String::from("Learn Rust"),
+                priority: None,
+                tags: Vec::new(),
+                project: None,
+                parent: None,
+                blocked_by: Vec::new(),
+                repeat: None,
+            },
+            command
+        );
+    }
+
+    #[test]
+    fn done_greenpath() {
+        let command = Command::parse(&args("done 3")).unwrap();
+
+        assert_eq!(Command::Done { id: 3, force: false }, command);
+        assert_eq!(
+            Command::Done { id: 3, force: true },
+            Command::parse(&args("done 3 --force")).unwrap()
+        );
+    }
+
+    #[test]
+    fn reopen_greenpath() {
+        let command = Command::parse(&args("reopen 3")).unwrap();
+
+        assert_eq!(Command::Reopen { id: 3 }, command);
+    }
+
+    #[test]
+    fn done_bad_id() {
+        let error = Command::parse(&args("done three")).err().unwrap();
+
+        assert_eq!("'done' requires a valid ID.", &error.message);
+    }
+
+    #[test]
+    fn edit_greenpath() {
+        let command = Command::parse(&args("edit 3 --title New title --due 2023-01-01")).unwrap();
+
+        assert_eq!(
+            Command::Edit {
+                id: 3,
+                due_date: Some(String::from("2023-01-01")),
+                title: Some(String::from("New title")),
+                priority: None,
+                tags: None,
+                parent: None,
+                blocked_by: None,
+                repeat: None,
+            },
+            command
+        );
+    }
+
+    #[test]
+    fn edit_priority_and_tags() {
+        let command = Command::parse(&args("edit 3 --priority none --tags ops web")).unwrap();
+
+        assert_eq!(
+            Command::Edit {
+                id: 3,
+                due_date: None,
+                title: None,
+                priority: Some(String::from("none")),
+                tags: Some(String::from("ops web")),
+                parent: None,
+                blocked_by: None,
+                repeat: None,
+            },
+            command
+        );
+    }
+
+    #[test]
+    fn blocked_by_options() {
+        let command =
+            Command::parse(&args("add 2023-01-01 Build --blocked-by 3 --blocked-by 4")).unwrap();
+        assert_eq!(
+            Command::Add {
+                input: String::from("2023-01-01 Build"),
+                priority: None,
+                tags: Vec::new(),
+                project: None,
+                parent: None,
+                blocked_by: vec![String::from("3"), String::from("4")],
+                repeat: None,
+            },
+            command
+        );
+
+        let command = Command::parse(&args("edit 7 --blocked-by 3 4")).unwrap();
+        assert_eq!(
+            Command::Edit {
+                id: 7,
+                due_date: None,
+                title: None,
+                priority: None,
+                tags: None,
+                parent: None,
+                blocked_by: Some(String::from("3 4")),
+                repeat: None,
+            },
+            command
+        );
+    }
+
+    #[test]
+    fn edit_requires_a_change() {
+        let error = Command::parse(&args("edit 3")).err().unwrap();
+
+        assert_eq!(
+            "'edit' requires at least one of --due, --title, --priority, --tags, --parent, --blocked-by or --repeat.",
+            &error.message
+        );
+    }
+
+    #[test]
+    fn edit_option_without_value() {
+        let error = Command::parse(&args("edit 3 --title --due 2023-01-01"))
+            .err()
+            .unwrap();
+
+        assert_eq!("--title requires a value.", &error.message);
+    }
+
+    #[test]
+    fn trash_commands() {
+        assert_eq!(Command::Delete { id: 1 }, Command::parse(&args("delete 1")).unwrap());
+        assert_eq!(Command::Restore { id: 1 }, Command::parse(&args("restore 1")).unwrap());
+        assert_eq!(Command::Trash, Command::parse(&args("trash")).unwrap());
+        assert_eq!(Command::EmptyTrash, Command::parse(&args("empty-trash")).unwrap());
+        assert!(Command::parse(&args("delete")).is_err());
+    }
+
+    #[test]
+    fn project_commands() {
+        assert_eq!(
+            Command::Project(ProjectCommand::Rename {
+                name: String::from("work"),
+                new_name: String::from("office"),
+            }),
+            Command::parse(&args("project rename work office")).unwrap()
+        );
+        assert_eq!(
+            Command::Move {
+                id: 2,
+                project: None
+            },
+            Command::parse(&args("move 2 none")).unwrap()
+        );
+        assert_eq!(Command::Projects, Command::parse(&args("projects")).unwrap());
+
+        let error = Command::parse(&args("project archive")).err().unwrap();
+        assert_eq!(
+            "'project archive' requires exactly one project name.",
+            &error.message
+        );
+    }
+
+    #[test]
+    fn parse_find() {
+        assert_eq!(
+            Command::Find {
+                text: String::from("dpl api")
+            },
+            Command::parse(&args("find dpl api")).unwrap()
+        );
+
+        let error = Command::parse(&args("find")).err().unwrap();
+        assert_eq!("'find' requires some text to search for.", &error.message);
+    }
+
+    #[test]
+    fn parse_agenda() {
+        assert_eq!(Command::Agenda, Command::parse(&args("agenda")).unwrap());
+    }
+
+    #[test]
+    fn view_commands() {
+        assert_eq!(Command::Views, Command::parse(&args("views")).unwrap());
+        assert_eq!(
+            Command::View(ViewCommand::Show {
+                name: String::from("this-week")
+            }),
+            Command::parse(&args("view this-week")).unwrap()
+        );
+        assert_eq!(
+            Command::View(ViewCommand::Save {
+                name: String::from("ops-overdue"),
+                query: String::from("status:overdue tag:ops sort:-due"),
+                columns: Some(String::from("id,due,title")),
+            }),
+            Command::parse(&args(
+                "view save ops-overdue status:overdue --columns id,due,title tag:ops sort:-due"
+            ))
+            .unwrap()
+        );
+        assert_eq!(
+            Command::View(ViewCommand::Delete {
+                name: String::from("ops")
+            }),
+            Command::parse(&args("view delete ops")).unwrap()
+        );
+
+        let error = Command::parse(&args("view save ops")).err().unwrap();
+        assert_eq!("'view save' requires a view name and a query.", &error.message);
+        assert!(Command::parse(&args("view")).is_err());
+        assert!(Command::parse(&args("view one two")).is_err());
+    }
+
+    #[test]
+    fn undo_redo_commands() {
+        assert_eq!(Command::Undo, Command::parse(&args("undo")).unwrap());
+        assert_eq!(Command::Redo, Command::parse(&args("redo")).unwrap());
+    }
+
+    #[test]
+    fn list_flags() {
+        assert_eq!(
+            Command::List(ListOptions::default()),
+            Command::parse(&args("list")).unwrap()
+        );
+        assert_eq!(
+            Command::List(ListOptions {
+                filter: ListFilter::Incomplete,
+                ..ListOptions::default()
+            }),
+            Command::parse(&args("list --incomplete")).unwrap()
+        );
+        assert_eq!(
+            Command::List(ListOptions {
+                filter: ListFilter::Incomplete,
+                tag: Some(String::from("ops")),
+                project: None,
+                tree: true,
+                sort: Some(String::from("priority")),
+                undated: Some(String::from("first")),
+                timestamps: Some(String::from("created,updated")),
+                query: None,
+            }),
+            Command::parse(&args(
+                "list --incomplete --tag ops --sort priority --undated first --tree --timestamps created,updated"
+            ))
+            .unwrap()
+        );
+        assert_eq!(
+            Command::List(ListOptions {
+                filter: ListFilter::Ready,
+                ..ListOptions::default()
+            }),
+            Command::parse(&args("list --ready")).unwrap()
+        );
+        assert_eq!(
+            Command::List(ListOptions {
+                filter: ListFilter::Incomplete,
+                query: Some(String::from("tag:ops -priority:low sort:-due")),
+                ..ListOptions::default()
+            }),
+            Command::parse(&args("list tag:ops --incomplete -priority:low sort:-due")).unwrap()
+        );
+        // The shell has already removed the quotes from title~"deploy to prod"
+        let list_args: Vec<String> = ["list", "title~deploy to prod", "-tag:ops", "due<=next fri"]
+            .iter()
+            .map(|arg| String::from(*arg))
+            .collect();
+        assert_eq!(
+            Command::List(ListOptions {
+                query: Some(String::from(r#"title~"deploy to prod" -tag:ops due<="next fri""#)),
+                ..ListOptions::default()
+            }),
+            Command::parse(&list_args).unwrap()
+        );
+        assert!(Command::parse(&args("list --bogus")).is_err());
+        assert!(Command::parse(&args("list --sort")).is_err());
+    }
+
+    #[test]
+    fn unknown_command() {
+        let error = Command::parse(&args("frobnicate")).err().unwrap();
+
+        assert_eq!("Unknown command 'frobnicate'.", &error.message);
+    }
+
+    #[test]
+    fn invocation_defaults() {
+        let invocation = Invocation::parse(&args(""), &Configuration::default()).unwrap();
+
+        assert_eq!(BackendKind::Json, invocation.backend);
+        assert_eq!("todo_store_data.json", invocation.store_filepath);
+        assert_eq!(None, invocation.command);
+    }
+
+    #[test]
+    fn invocation_global_options() {
+        let invocation =
+            Invocation::parse(&args("--backend sqlite --store my.db list"), &Configuration::default()).unwrap();
+
+        assert_eq!(BackendKind::Sqlite, invocation.backend);
+        assert_eq!("my.db", invocation.store_filepath);
+        assert_eq!(Some(Command::List(ListOptions::default())), invocation.command);
+    }
+
+    #[test]
+    fn invocation_configured_backend() {
+        let configuration = Configuration {
+            backend: Some(String::from("sqlite")),
+            ..Configuration::default()
+        };
+        let invocation = Invocation::parse(&args("history"), &configuration).unwrap();
+        assert_eq!(BackendKind::Sqlite, invocation.backend);
+        assert_eq!("todo_store_data.sqlite", invocation.store_filepath);
+
+        let invocation = Invocation::parse(&args("--backend json"), &configuration).unwrap();
+        assert_eq!(BackendKind::Json, invocation.backend);
+    }
+
+    #[test]
+    fn invocation_max_title_length() {
+        let configuration = Configuration {
+            max_title_length: Some(String::from("80")),
+            ..Configuration::default()
+        };
+        let invocation = Invocation::parse(&args("list"), &configuration).unwrap();
+        assert_eq!(Some(80), invocation.max_title_length);
+
+        let invocation =
+            Invocation::parse(&args("--max-title-length 120 list"), &configuration).unwrap();
+        assert_eq!(Some(120), invocation.max_title_length);
+
+        let error = Invocation::parse(&args("--max-title-length 0"), &Configuration::default())
+            .err()
+            .unwrap();
+        assert_eq!(
+            "Invalid max title length '0'. Expected a positive number.",
+            &error.message
+        );
+    }
+
+    #[test]
+    fn invocation_unknown_backend() {
+        let error = Invocation::parse(&args("--backend csv"), &Configuration::default()).err().unwrap();
+
+        assert_eq!(
+            "Unknown storage backend 'csv'. Expected json or sqlite.",
+            &error.message
+        );
+    }
+}
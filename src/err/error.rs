@@ -8,7 +8,7 @@ pub struct TodoError {
 }
 
 impl TodoError {
-    pub fn new(message: String, source: Box<(dyn Error)>) -> TodoError {
+    pub fn new(message: String, source: Box<dyn Error>) -> TodoError {
         TodoError {
             message,
             source: Some(source),
@@ -1,17 +1,37 @@
 use std::{fmt, io, process};
 use std::io::Write;
 
+pub mod cli;
 pub mod err;
 pub mod todo;
 
 use err::TodoError;
-use todo::{TodoItem, TodoStore};
+use todo::{
+    ItemChanges, Priority, Query, Recurrence, SavedView, SortOrder, Timestamp, TodoItem,
+    TodoStore, UndatedPosition,
+};
 
 // TODO: Fix deserialization error loop
 enum MenuAction {
     ListIncompleteItems,
+    ListAgenda,
     CreateItem,
     MarkItemComplete,
+    ReopenItem,
+    EditItem,
+    DeleteItem,
+    ListTrash,
+    EmptyTrash,
+    Undo,
+    Redo,
+    SortItems,
+    FilterByTag,
+    SearchItems,
+    FindItems,
+    ManageProjects,
+    MoveItem,
+    ShowTree,
+    ListReadyItems,
     ListCompletedItems,
     ListAllItems,
     Quit,
@@ -24,7 +44,7 @@ struct MenuItem {
 }
 
 impl MenuItem {
-    pub fn parse_user_selection(input: &String) -> Result<&'static MenuItem, TodoError> {
+    pub fn parse_user_selection(input: &str) -> Result<&'static MenuItem, TodoError> {
         let input: char = input.trim().parse::<char>().map_err(|err| {
             TodoError::new(
                 String::from("Input must be a single character."),
@@ -41,43 +61,139 @@ impl MenuItem {
     }
 }
 
-const MENU_ITER: [MenuItem; 6] = [
+const MENU_ITER: [MenuItem; 22] = [
     LIST_INCOMPLETE_ITEMS,
+    LIST_AGENDA,
+    LIST_READY_ITEMS,
     LIST_ALL_ITEMS,
     LIST_COMPLETED_ITEMS,
+    SHOW_TREE,
+    FILTER_BY_TAG,
+    SEARCH_ITEMS,
+    FIND_ITEMS,
+    MANAGE_PROJECTS,
+    LIST_TRASH,
+    SORT_ITEMS,
     CREATE_ITEM,
     COMPLETE_ITEM,
+    REOPEN_ITEM,
+    EDIT_ITEM,
+    MOVE_ITEM,
+    DELETE_ITEM,
+    EMPTY_TRASH,
+    UNDO,
+    REDO,
     QUIT,
 ];
 
 const LIST_INCOMPLETE_ITEMS: MenuItem = MenuItem {
     action: MenuAction::ListIncompleteItems,
-    title: &"List [i]ncomplete items",
+    title: "List [i]ncomplete items",
     selection: 'i',
 };
+const LIST_AGENDA: MenuItem = MenuItem {
+    action: MenuAction::ListAgenda,
+    title: "[L]ist agenda",
+    selection: 'l',
+};
+const LIST_READY_ITEMS: MenuItem = MenuItem {
+    action: MenuAction::ListReadyItems,
+    title: "List read[y] items",
+    selection: 'y',
+};
 const LIST_ALL_ITEMS: MenuItem = MenuItem {
     action: MenuAction::ListAllItems,
-    title: &"List [a]ll items",
+    title: "List [a]ll items",
     selection: 'a',
 };
 const LIST_COMPLETED_ITEMS: MenuItem = MenuItem {
     action: MenuAction::ListCompletedItems,
-    title: &"List [h]istory",
+    title: "List [h]istory",
     selection: 'h',
 };
+const SORT_ITEMS: MenuItem = MenuItem {
+    action: MenuAction::SortItems,
+    title: "[S]ort items",
+    selection: 's',
+};
 const CREATE_ITEM: MenuItem = MenuItem {
     action: MenuAction::CreateItem,
-    title: &"Create [n]ew item",
+    title: "Create [n]ew item",
     selection: 'n',
 };
 const COMPLETE_ITEM: MenuItem = MenuItem {
     action: MenuAction::MarkItemComplete,
-    title: &"[C]omplete item",
+    title: "[C]omplete item",
     selection: 'c',
 };
+const REOPEN_ITEM: MenuItem = MenuItem {
+    action: MenuAction::ReopenItem,
+    title: "Re[o]pen item",
+    selection: 'o',
+};
+const EDIT_ITEM: MenuItem = MenuItem {
+    action: MenuAction::EditItem,
+    title: "[E]dit item",
+    selection: 'e',
+};
+const DELETE_ITEM: MenuItem = MenuItem {
+    action: MenuAction::DeleteItem,
+    title: "[D]elete item",
+    selection: 'd',
+};
+const FILTER_BY_TAG: MenuItem = MenuItem {
+    action: MenuAction::FilterByTag,
+    title: "Filter by ta[g]",
+    selection: 'g',
+};
+const SEARCH_ITEMS: MenuItem = MenuItem {
+    action: MenuAction::SearchItems,
+    title: "Search [b]y query",
+    selection: 'b',
+};
+const FIND_ITEMS: MenuItem = MenuItem {
+    action: MenuAction::FindItems,
+    title: "[F]ind items",
+    selection: 'f',
+};
+const SHOW_TREE: MenuItem = MenuItem {
+    action: MenuAction::ShowTree,
+    title: "Sho[w] subtask tree",
+    selection: 'w',
+};
+const MANAGE_PROJECTS: MenuItem = MenuItem {
+    action: MenuAction::ManageProjects,
+    title: "[P]rojects",
+    selection: 'p',
+};
+const MOVE_ITEM: MenuItem = MenuItem {
+    action: MenuAction::MoveItem,
+    title: "Mo[v]e item to project",
+    selection: 'v',
+};
+const LIST_TRASH: MenuItem = MenuItem {
+    action: MenuAction::ListTrash,
+    title: "List [t]rash / restore",
+    selection: 't',
+};
+const EMPTY_TRASH: MenuItem = MenuItem {
+    action: MenuAction::EmptyTrash,
+    title: "E[x]punge trash",
+    selection: 'x',
+};
+const UNDO: MenuItem = MenuItem {
+    action: MenuAction::Undo,
+    title: "[U]ndo",
+    selection: 'u',
+};
+const REDO: MenuItem = MenuItem {
+    action: MenuAction::Redo,
+    title: "[R]edo",
+    selection: 'r',
+};
 const QUIT: MenuItem = MenuItem {
     action: MenuAction::Quit,
-    title: &"[Q]uit...",
+    title: "[Q]uit...",
     selection: 'q',
 };
 
@@ -97,30 +213,132 @@ pub fn run(store: &mut TodoStore) -> Result<(), Box<TodoError>> {
     // Write data to file for each update
     // get_menu_action();
 
-    print_menu();
+    print_menu(store);
 
-    let menu_item_selection = get_menu_action()?;
+    let user_selection = read_user_input()?;
+    // Saved views are picked by their number in the menu
+    if let Some(view) = select_saved_view(store, &user_selection) {
+        todo::print_view(store, &view)?;
+        return Ok(());
+    }
+    let menu_item_selection = MenuItem::parse_user_selection(&user_selection)?;
     // println!("Selected: {}", menu_item_selection.name);
 
     match menu_item_selection.action {
         MenuAction::ListIncompleteItems => {
-            todo::print_store("Incomplete items", &store.list_incomplete_todos());
+            todo::print_store(store, "Incomplete items", &store.list_incomplete_todos());
+        }
+        MenuAction::ListAgenda => {
+            todo::print_agenda(store);
+        }
+        MenuAction::ListReadyItems => {
+            todo::print_store(store, "Ready items", &store.list_ready());
         }
         MenuAction::ListAllItems => {
-            todo::print_store("All items", &store.list_all_todos());
+            todo::print_store(store, "All items", &store.list_all_todos());
         }
         MenuAction::ListCompletedItems => {
-            todo::print_store("Completed items", &store.list_history());
+            todo::print_history(store);
         }
         MenuAction::CreateItem => {
-            store.create_new_todo()?;
+            create_new_todo(store)?;
         }
         MenuAction::MarkItemComplete => {
-            todo::print_store("Incomplete items", &store.list_incomplete_todos());
-            store.mark_as_done()?;
+            todo::print_store(store, "Incomplete items", &store.list_incomplete_todos());
+            mark_as_done(store)?;
+        }
+        MenuAction::ReopenItem => {
+            todo::print_history(store);
+            if let Some(id) = read_item_id("Enter the ID of the item to reopen or return to [m]enu:")? {
+                store.reopen(id)?;
+            }
+        }
+        MenuAction::EditItem => {
+            todo::print_store(store, "All items", &store.list_all_todos());
+            edit_todo(store)?;
+        }
+        MenuAction::DeleteItem => {
+            todo::print_store(store, "All items", &store.list_all_todos());
+            if let Some(id) = read_item_id("Enter the ID of the item to delete or return to [m]enu:")? {
+                store.delete(id)?;
+            }
+        }
+        MenuAction::FilterByTag => {
+            println!("Enter a tag or return to [m]enu:");
+            let tag = read_user_input()?;
+            if !is_return_to_menu(&tag) {
+                let tag = TodoItem::parse_tag(&tag)?;
+                todo::print_store(store, &format!("Items tagged #{}", tag), &store.list_tagged(&tag));
+            }
+        }
+        MenuAction::SearchItems => {
+            println!("Enter a query or return to [m]enu:");
+            println!("e.g. status:open due<+7d tag:ops title~\"deploy\" sort:-due");
+            let input = read_user_input()?;
+            if !is_return_to_menu(&input) {
+                let query = Query::parse(input.trim())?;
+                todo::print_store(store, &format!("Items matching {}", input.trim()), &store.search(&query));
+
+                println!("Enter a name to save this search as a view, or press return to skip:");
+                let name = read_user_input()?;
+                if !name.trim().is_empty() {
+                    let view = store.save_view(SavedView::new(&name, &input, Vec::new())?)?;
+                    println!("Saved view {}.", view.name);
+                }
+            }
+        }
+        MenuAction::FindItems => {
+            find_items(store)?;
+        }
+        MenuAction::ShowTree => {
+            todo::print_tree(store, "All items", &store.list_all_todos());
+        }
+        MenuAction::ManageProjects => {
+            todo::print_projects(store);
+            manage_projects(store)?;
+        }
+        MenuAction::MoveItem => {
+            todo::print_store(store, "All items", &store.list_all_todos());
+            move_todo(store)?;
+        }
+        MenuAction::ListTrash => {
+            todo::print_store(store, "Trash", &store.list_trash());
+            if let Some(id) = read_item_id("Enter the ID of the item to restore or return to [m]enu:")? {
+                store.restore(id)?;
+            }
+        }
+        MenuAction::EmptyTrash => {
+            let removed_item_count = store.empty_trash()?;
+            println!("Permanently removed {} item(s) from the trash.", removed_item_count);
+        }
+        MenuAction::SortItems => {
+            println!("Sort by [d]ue date or [p]riority:");
+            let sort_order = match read_user_input()?.trim() {
+                "d" => SortOrder::DueDate,
+                "p" => SortOrder::Priority,
+                other => SortOrder::parse(other)?,
+            };
+            store.sort_by(sort_order);
+
+            println!("Show items without a due date [f]irst or [l]ast:");
+            let undated_position = match read_user_input()?.trim() {
+                "f" => UndatedPosition::First,
+                "l" => UndatedPosition::Last,
+                other => UndatedPosition::parse(other)?,
+            };
+            store.place_undated(undated_position);
+
+            println!("Show when items were created, completed and/or updated (e.g. created,updated), or leave blank for none:");
+            store.show_timestamps(Timestamp::parse_list(&read_user_input()?)?);
+        }
+        MenuAction::Undo => {
+            println!("Undid {}.", store.undo()?);
+        }
+        MenuAction::Redo => {
+            println!("Redid {}.", store.redo()?);
         }
         MenuAction::Quit => {
-            // TODO: Save state
+            store.compact()?;
             println!("Goodbye.");
             process::exit(0);
         }
@@ -130,28 +348,329 @@ pub fn run(store: &mut TodoStore) -> Result<(), Box<TodoError>> {
     Ok(())
 }
 
-fn get_menu_action() -> Result<&'static MenuItem, TodoError> {
+fn select_saved_view(store: &TodoStore, user_selection: &str) -> Option<SavedView> {
+    let number = user_selection.trim().parse::<usize>().ok()?;
+
+    store.list_views().get(number.checked_sub(1)?).map(|view| (*view).clone())
+}
+
+fn create_new_todo(store: &mut TodoStore) -> Result<(), TodoError> {
+    println!("Enter a new Todo Item or return to [m]enu:");
+    println!(
+        "Format: [{{Due date}} [Due time]] {{Title}} [!high|!medium|!low] [#tag...] [@project] [^parent ID] [*repeat rule]"
+    );
+    println!("Due dates: YYYY-MM-DD, today, tomorrow, on fri, next monday, +3d, in 2 weeks or eom");
+    println!("Due times: 15:00 or 3pm, optionally followed by UTC or an offset like +02:00");
+
+    let new_todo = read_user_input()?;
+    if is_return_to_menu(&new_todo) {
+        return Ok(());
+    }
+
+    let input = store.parse_input(&new_todo)?;
+    store.create(input)?;
+
+    Ok(())
+}
+
+fn mark_as_done(store: &mut TodoStore) -> Result<(), TodoError> {
+    match read_item_id("Enter the ID of the completed item or return to [m]enu:")? {
+        Some(id) => complete_item(store, id),
+        None => Ok(()),
+    }
+}
+
+// Asks before also completing open subtasks or ignoring open blockers
+fn complete_item(store: &mut TodoStore, id: usize) -> Result<(), TodoError> {
+    let open_subtask_count = store
+        .list_subtasks(id)
+        .iter()
+        .filter(|item| !item.complete)
+        .count();
+    if open_subtask_count > 0 {
+        println!(
+            "Item {} has {} open subtask(s). Complete them as well? [y/n]",
+            id, open_subtask_count
+        );
+        if !read_user_input()?.trim().eq_ignore_ascii_case("y") {
+            return Ok(());
+        }
+        return store.force_complete(id);
+    }
+
+    let open_blockers = store.open_blockers(store.get_item(id)?);
+    if !open_blockers.is_empty() {
+        println!(
+            "Item {} is blocked by {} open item(s). Complete it anyway? [y/n]",
+            id,
+            open_blockers.len()
+        );
+        if !read_user_input()?.trim().eq_ignore_ascii_case("y") {
+            return Ok(());
+        }
+        return store.force_complete(id);
+    }
+
+    store.complete(id)
+}
+
+fn edit_todo(store: &mut TodoStore) -> Result<(), TodoError> {
+    match read_item_id("Enter the ID of the item to edit or return to [m]enu:")? {
+        Some(id) => edit_item(store, id),
+        None => Ok(()),
+    }
+}
+
+fn edit_item(store: &mut TodoStore, id: usize) -> Result<(), TodoError> {
+    let item = store.get_item(id)?;
+    let (current_due, current_title, current_priority, current_tags, current_parent) = (
+        item.due_display(),
+        item.title.clone(),
+        item.priority,
+        item.tags_display(),
+        item.parent,
+    );
+    let current_recurrence = item
+        .recurrence
+        .as_ref()
+        .map_or(String::from("none"), |recurrence| recurrence.to_string());
+    let current_blockers = item
+        .blocked_by
+        .iter()
+        .map(|blocker| blocker.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    // Blank input keeps the current value
+    println!(
+        "Enter a new due date and optional time (none for no due date) or leave blank to keep {}:",
+        match current_due.as_str() {
+            "" => "none",
+            current_due => current_due,
+        }
+    );
+    let due = read_user_input()?;
+    let due = match due.trim() {
+        "" => None,
+        due => Some(TodoItem::parse_due(due)?),
+    };
+
+    println!("Enter a new title or leave blank to keep \"{}\":", current_title);
+    let title = read_user_input()?;
+    let title = match title.trim() {
+        "" => None,
+        title => Some(String::from(title)),
+    };
+
+    println!(
+        "Enter a new priority (high, medium, low or none) or leave blank to keep {}:",
+        current_priority.map_or("none", |priority| priority.as_str())
+    );
+    let priority = read_user_input()?;
+    let priority = match priority.trim() {
+        "" => None,
+        priority => Some(Priority::parse_optional(priority)?),
+    };
+
+    println!(
+        "Enter new tags separated by spaces (none to clear) or leave blank to keep \"{}\":",
+        current_tags
+    );
+    let tags = read_user_input()?;
+    let tags = match tags.trim() {
+        "" => None,
+        tags => Some(TodoItem::parse_tags(tags)?),
+    };
+
+    println!(
+        "Enter the ID of a new parent item (none for no parent) or leave blank to keep {}:",
+        current_parent.map_or(String::from("none"), |parent| parent.to_string())
+    );
+    let parent = read_user_input()?;
+    let parent = match parent.trim() {
+        "" => None,
+        "none" => Some(None),
+        parent => Some(Some(TodoItem::parse_parent_id(parent)?)),
+    };
+
+    println!(
+        "Enter the IDs of the items blocking this one (none to clear) or leave blank to keep \"{}\":",
+        current_blockers
+    );
+    let blocked_by = read_user_input()?;
+    let blocked_by = match blocked_by.trim() {
+        "" => None,
+        blocked_by => Some(TodoItem::parse_blockers(blocked_by)?),
+    };
+
+    println!(
+        "Enter a new repeat rule (daily, weekly[:mon,...], monthly[:DAY], after:Nd or none) or leave blank to keep {}:",
+        current_recurrence
+    );
+    let recurrence = read_user_input()?;
+    let recurrence = match recurrence.trim() {
+        "" => None,
+        recurrence => Some(Recurrence::parse_optional(recurrence)?),
+    };
+
+    let changes = ItemChanges {
+        title,
+        due_date: due.map(|(due_date, _)| due_date),
+        due_time: due.map(|(_, due_time)| due_time),
+        priority,
+        tags,
+        project: None,
+        parent,
+        blocked_by,
+        recurrence,
+    };
+    if changes.is_empty() {
+        return Ok(());
+    }
+    store.edit(id, changes)?;
+
+    Ok(())
+}
+
+fn manage_projects(store: &mut TodoStore) -> Result<(), TodoError> {
+    println!("Enter [v]iew, [n]ew, [r]ename, [a]rchive, [u]narchive or return to [m]enu:");
+    let selection = read_user_input()?;
+    if is_return_to_menu(&selection) {
+        return Ok(());
+    }
+
+    println!("Enter the project name:");
+    let name = read_user_input()?;
+    match selection.trim() {
+        "v" => {
+            let title = format!("Project: {}", store.get_project(&name)?);
+            todo::print_store(store, &title, &store.list_project(&name)?);
+        }
+        "n" => {
+            store.create_project(&name)?;
+        }
+        "r" => {
+            println!("Enter the new project name:");
+            let new_name = read_user_input()?;
+            store.rename_project(&name, &new_name)?;
+        }
+        "a" => store.archive_project(&name)?,
+        "u" => store.unarchive_project(&name)?,
+        _ => {
+            return Err(TodoError::new_from_msg(String::from(
+                "A valid project action must be selected.",
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+fn move_todo(store: &mut TodoStore) -> Result<(), TodoError> {
+    let id = match read_item_id("Enter the ID of the item to move or return to [m]enu:")? {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+
+    println!("Enter the project to move it to, or none to take it out of its project:");
+    let project = read_user_input()?;
+    let project = match project.trim() {
+        "none" => None,
+        project => Some(project),
+    };
+    store.move_item(id, project)?;
+
+    Ok(())
+}
+
+// Fuzzy search of titles, then complete or edit one of the results
+fn find_items(store: &mut TodoStore) -> Result<(), TodoError> {
+    println!("Enter text to find or return to [m]enu:");
+    let text = read_user_input()?;
+    if is_return_to_menu(&text) || text.trim().is_empty() {
+        return Ok(());
+    }
+
+    let matches = store.find(text.trim());
+    if matches.is_empty() {
+        println!("No items match '{}'.", text.trim());
+        return Ok(());
+    }
+    todo::print_matches(store, &format!("Items matching '{}'", text.trim()), &matches);
+    let ids: Vec<usize> = matches.iter().map(|(item, _)| item.id).collect();
+
+    println!("Enter [c] or [e] and an ID to complete or edit a result (e.g. c 3), or press return to go back to the menu:");
+    let input = read_user_input()?;
+    let (action, id) = match input.split_whitespace().collect::<Vec<&str>>()[..] {
+        [] => return Ok(()),
+        [action, id] => (action.to_lowercase(), id),
+        _ => {
+            return Err(TodoError::new_from_msg(String::from(
+                "Input must be c or e followed by an ID.",
+            )))
+        }
+    };
+    let id = id
+        .parse::<usize>()
+        .map_err(|err| TodoError::new(String::from("Input must be an ID."), Box::new(err)))?;
+    if !ids.contains(&id) {
+        return Err(TodoError::new_from_msg(format!(
+            "Item {} is not one of the results.",
+            id
+        )));
+    }
+
+    match action.as_str() {
+        "c" => complete_item(store, id),
+        "e" => edit_item(store, id),
+        _ => Err(TodoError::new_from_msg(String::from(
+            "Input must be c or e followed by an ID.",
+        ))),
+    }
+}
+
+// Prompt for an item ID. Returns None if the user chose to return to the menu.
+fn read_item_id(prompt: &str) -> Result<Option<usize>, TodoError> {
+    println!("{}", prompt);
+
+    let id = read_user_input()?;
+    if is_return_to_menu(&id) {
+        return Ok(None);
+    }
+
+    // Validate and parse ID
+    id.trim()
+        .parse::<usize>()
+        .map(Some)
+        .map_err(|err| TodoError::new(String::from("Input must be an ID."), Box::new(err)))
+}
+
+fn read_user_input() -> Result<String, TodoError> {
     print!("> ");
     io::stdout().flush().unwrap();
 
-    let mut user_selection = String::new();
+    let mut input = String::new();
     io::stdin()
-        .read_line(&mut user_selection)
+        .read_line(&mut input)
         .map_err(|err| TodoError::new(String::from("Failed to read line."), Box::new(err)))?;
 
-    MenuItem::parse_user_selection(&user_selection)
+    Ok(input)
+}
+
+fn is_return_to_menu(input: &str) -> bool {
+    input.trim().eq_ignore_ascii_case("m")
 }
 
-fn print_menu() {
+fn print_menu(store: &TodoStore) {
     println!("\nPlease select an action:");
 
     let column_page_size = (MENU_ITER.len() / 2) + (MENU_ITER.len() % 2);
 
-    for i in 0..column_page_size {
-        let buffer_length = MENU_COLUMN_WIDTH - format!("{}", MENU_ITER[i]).len();
+    for (i, menu_item) in MENU_ITER.iter().enumerate().take(column_page_size) {
+        let buffer_length = MENU_COLUMN_WIDTH.saturating_sub(format!("{}", menu_item).len());
         let buffer = String::from(" ").repeat(buffer_length);
 
-        let column_one_title = format!("{}", MENU_ITER[i]) + &buffer;
+        let column_one_title = format!("{}", menu_item) + &buffer;
         let column_two_title = MENU_ITER
             .get(i + column_page_size)
             .map(|item| format!("{}", item))
@@ -159,4 +678,12 @@ fn print_menu() {
 
         println!("{}{}", column_one_title, column_two_title);
     }
+
+    let views = store.list_views();
+    if !views.is_empty() {
+        println!("Saved views:");
+        for (i, view) in views.iter().enumerate() {
+            println!("{} - {}", i + 1, view.name);
+        }
+    }
 }
@@ -1,19 +1,52 @@
 extern crate core;
 
-use todo_assistant::todo::TodoStore;
+use std::{env, process};
 
-const PERSISTENCE_STORE_FILENAME: &str = "todo_store_data.json";
+use todo_assistant::cli::{self, Configuration, Invocation};
+use todo_assistant::err::TodoError;
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    let mut store = TodoStore::new_from_persistence(PERSISTENCE_STORE_FILENAME).unwrap();
+    // Parse the command before touching the store so usage errors never depend on its state
+    let invocation = match Invocation::parse(&args, &Configuration::from_env()) {
+        Ok(invocation) => invocation,
+        Err(e) => {
+            print_error(&e);
+            eprintln!("\n{}", cli::USAGE);
+            process::exit(cli::EXIT_USAGE);
+        }
+    };
+
+    let mut store = match invocation.open_store() {
+        Ok(store) => store,
+        Err(e) => {
+            print_error(&e);
+            process::exit(cli::EXIT_FAILURE);
+        }
+    };
+
+    // Non-interactive mode: execute a single command and exit
+    if let Some(command) = invocation.command {
+        match cli::execute(command, &mut store) {
+            Ok(()) => process::exit(cli::EXIT_SUCCESS),
+            Err(e) => {
+                print_error(&e);
+                process::exit(cli::EXIT_FAILURE);
+            }
+        }
+    }
 
     loop {
         if let Err(e) = todo_assistant::run(&mut store) {
-            eprintln!("Error: {}", e);
-            if e.root().is_some() {
-                eprintln!("Root cause: {}", e.root().as_ref().unwrap());
-            }
+            print_error(&e);
         }
     }
 }
+
+fn print_error(e: &TodoError) {
+    eprintln!("Error: {}", e);
+    if let Some(root) = e.root() {
+        eprintln!("Root cause: {}", root);
+    }
+}
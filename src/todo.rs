@@ -1,6 +1,31 @@
+mod action;
+mod backend;
+mod due_date;
+mod fuzzy;
 mod item;
+mod journal;
+mod lock;
+mod migration;
+mod project;
+mod query;
+mod recurrence;
 mod store;
+mod view;
 
-pub use item::{TodoItem, TodoItemSerializable};
-pub use store::todo_printer::print_store;
-pub use store::todo_store::TodoStore;
+pub use action::StoreAction;
+pub use backend::{
+    BackendKind, JsonBackend, LoadedStore, SqliteBackend, StorageBackend, StoreChange,
+    StoreSnapshot,
+};
+pub use due_date::DueTime;
+pub use fuzzy::FuzzyMatch;
+pub use item::{ItemChanges, ItemInput, Priority, Timestamp, TodoItem, TodoItemSerializable};
+pub use project::Project;
+pub use query::Query;
+pub use recurrence::Recurrence;
+pub use store::todo_printer::{
+    print_agenda, print_agenda_relative_to, print_history, print_matches, print_projects,
+    print_store, print_tree, print_view, print_views,
+};
+pub use store::todo_store::{SortOrder, TodoStore, UndatedPosition};
+pub use view::{Column, SavedView};
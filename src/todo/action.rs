@@ -0,0 +1,132 @@
+use chrono::{DateTime, Utc};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+use crate::todo::{Project, SavedView, TodoItemSerializable};
+
+// A single mutation of the store. Every change is applied through one of these so the exact same
+// code path is used for live edits and for replaying changes a backend has not yet snapshotted.
+// The derived (de)serializers are wrapped by the impls below, see LegacyEdit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(remote = "Self", tag = "action", rename_all = "snake_case")]
+pub enum StoreAction {
+    Create { item: TodoItemSerializable },
+    // at is recorded with the action so replaying it keeps the original time. Changes journaled
+    // before timestamps existed have none.
+    Complete {
+        id: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        at: Option<DateTime<Utc>>,
+    },
+    Reopen {
+        id: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        at: Option<DateTime<Utc>>,
+    },
+    // Replace an item with an edited version of it
+    Edit { item: TodoItemSerializable },
+    // Change the title and due date of an item. Edits were journaled and kept in the undo history
+    // in this shape before items had more fields, and are read back as this action.
+    LegacyEdit {
+        id: usize,
+        title: String,
+        due_date: String,
+    },
+    Delete { id: usize },
+    Restore { id: usize },
+    EmptyTrash,
+    // Permanently remove an item from the store without going through the trash. Only produced as
+    // the inverse of Create.
+    Remove { id: usize },
+    // Put items back into the trash. Only produced as the inverse of EmptyTrash.
+    ReturnToTrash { items: Vec<TodoItemSerializable> },
+    CreateProject { project: Project },
+    // Rename a project along with every item that belongs to it
+    RenameProject { name: String, new_name: String },
+    ArchiveProject { name: String },
+    UnarchiveProject { name: String },
+    // Only produced as the inverse of CreateProject
+    RemoveProject { name: String },
+    // Add a saved view, replacing any view with the same name
+    SaveView { view: SavedView },
+    RemoveView { name: String },
+    // Several actions applied in order and undone as one
+    Batch { actions: Vec<StoreAction> },
+    // Revert the most recent command on the undo stack
+    Undo,
+    // Re-apply the most recently undone command
+    Redo,
+}
+
+// An action paired with the action that reverts it. The inverse is captured from the store state
+// just before the action is applied.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReversibleCommand {
+    pub action: StoreAction,
+    pub inverse: StoreAction,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UndoHistory {
+    pub undo: Vec<ReversibleCommand>,
+    pub redo: Vec<ReversibleCommand>,
+}
+
+impl Serialize for StoreAction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StoreAction::serialize(self, serializer)
+    }
+}
+
+// Old edits share the "edit" tag with the current ones, so they are told apart by their fields
+impl<'de> Deserialize<'de> for StoreAction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<StoreAction, D::Error> {
+        let mut value = serde_json::Value::deserialize(deserializer)?;
+        if value["action"] == "edit" && value.get("item").is_none() {
+            value["action"] = serde_json::Value::from("legacy_edit");
+        }
+
+        StoreAction::deserialize(value).map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for StoreAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreAction::Create { item } => write!(f, "create item {}", item.id),
+            StoreAction::Complete { id, .. } => write!(f, "complete item {}", id),
+            StoreAction::Reopen { id, .. } => write!(f, "reopen item {}", id),
+            StoreAction::Edit { item } => write!(f, "edit item {}", item.id),
+            StoreAction::LegacyEdit { id, .. } => write!(f, "edit item {}", id),
+            StoreAction::Delete { id } => write!(f, "delete item {}", id),
+            StoreAction::Restore { id } => write!(f, "restore item {}", id),
+            StoreAction::EmptyTrash => write!(f, "empty trash"),
+            StoreAction::Remove { id } => write!(f, "remove item {}", id),
+            StoreAction::ReturnToTrash { items } => {
+                write!(f, "return {} item(s) to the trash", items.len())
+            }
+            StoreAction::CreateProject { project } => {
+                write!(f, "create project {}", project.name)
+            }
+            StoreAction::RenameProject { name, new_name } => {
+                write!(f, "rename project {} to {}", name, new_name)
+            }
+            StoreAction::ArchiveProject { name } => write!(f, "archive project {}", name),
+            StoreAction::UnarchiveProject { name } => write!(f, "unarchive project {}", name),
+            StoreAction::RemoveProject { name } => write!(f, "remove project {}", name),
+            StoreAction::SaveView { view } => write!(f, "save view {}", view.name),
+            StoreAction::RemoveView { name } => write!(f, "remove view {}", name),
+            StoreAction::Batch { actions } => write!(
+                f,
+                "{}",
+                actions
+                    .iter()
+                    .map(StoreAction::to_string)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            StoreAction::Undo => write!(f, "undo"),
+            StoreAction::Redo => write!(f, "redo"),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+mod json;
+mod sqlite;
+
+use crate::err::TodoError;
+use crate::todo::action::{StoreAction, UndoHistory};
+use crate::todo::{Project, SavedView, TodoItemSerializable};
+
+pub use json::JsonBackend;
+pub use sqlite::SqliteBackend;
+
+// The complete persisted state of a store
+pub struct StoreSnapshot {
+    pub items: Vec<TodoItemSerializable>,
+    pub trash: Vec<TodoItemSerializable>,
+    pub projects: Vec<Project>,
+    pub views: Vec<SavedView>,
+    pub next_id: usize,
+    pub history: UndoHistory,
+}
+
+pub struct LoadedStore {
+    pub snapshot: StoreSnapshot,
+    // Actions recorded after the snapshot was taken. They are applied in order on top of it.
+    pub pending_actions: Vec<StoreAction>,
+    // Set when the backend wants a fresh snapshot written once the pending actions are applied,
+    // e.g. after recovering from a crash or upgrading an old file
+    pub needs_snapshot: bool,
+}
+
+// A single applied action together with the resulting state of everything it touched
+pub struct StoreChange<'a> {
+    pub action: &'a StoreAction,
+    // Touched items that are now in the store
+    pub items: Vec<TodoItemSerializable>,
+    // Touched items that are now in the trash
+    pub trashed: Vec<TodoItemSerializable>,
+    // Touched items that no longer exist anywhere
+    pub removed_ids: Vec<usize>,
+    pub projects: &'a [Project],
+    pub views: &'a [SavedView],
+    pub next_id: usize,
+    pub history: &'a UndoHistory,
+}
+
+pub trait StorageBackend {
+    fn load(&mut self) -> Result<LoadedStore, TodoError>;
+
+    // Durably record a change that has just been applied to the in-memory store
+    fn apply_change(&mut self, change: &StoreChange) -> Result<(), TodoError>;
+
+    // Replace everything persisted with the given snapshot
+    fn snapshot(&mut self, snapshot: &StoreSnapshot) -> Result<(), TodoError>;
+
+    // Number of changes recorded since the last snapshot. Backends that persist every change in
+    // place never need a snapshot and report zero.
+    fn pending_change_count(&self) -> usize {
+        0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackendKind {
+    Json,
+    Sqlite,
+}
+
+impl BackendKind {
+    pub fn parse(name: &str) -> Result<BackendKind, TodoError> {
+        match name.trim().to_lowercase().as_str() {
+            "json" => Ok(BackendKind::Json),
+            "sqlite" => Ok(BackendKind::Sqlite),
+            other => Err(TodoError::new_from_msg(format!(
+                "Unknown storage backend '{}'. Expected json or sqlite.",
+                other
+            ))),
+        }
+    }
+
+    pub fn default_filepath(&self) -> &'static str {
+        match self {
+            BackendKind::Json => "todo_store_data.json",
+            BackendKind::Sqlite => "todo_store_data.sqlite",
+        }
+    }
+
+    pub fn open(&self, filepath: &str) -> Result<Box<dyn StorageBackend>, TodoError> {
+        Ok(match self {
+            BackendKind::Json => Box::new(JsonBackend::open(filepath)?),
+            BackendKind::Sqlite => Box::new(SqliteBackend::open(filepath)?),
+        })
+    }
+}
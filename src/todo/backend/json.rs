@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+use crate::err::TodoError;
+use crate::todo::action::UndoHistory;
+use crate::todo::backend::{LoadedStore, StorageBackend, StoreChange, StoreSnapshot};
+use crate::todo::journal::Journal;
+use crate::todo::lock::StoreLock;
+use crate::todo::migration;
+use crate::todo::{Project, SavedView, TodoItemSerializable};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TodoStoreSerializable {
+    schema_version: u64,
+    store: Vec<TodoItemSerializable>,
+    trash: Vec<TodoItemSerializable>,
+    projects: Vec<Project>,
+    views: Vec<SavedView>,
+    next_id: usize,
+    history: UndoHistory,
+    #[serde(default)]
+    last_journal_seq: u64,
+}
+
+// Stores a JSON snapshot of the whole store and appends every change to a journal next to it.
+// The journal is folded back into the snapshot whenever the store asks for one.
+pub struct JsonBackend {
+    filepath: String,
+    journal: Journal,
+    _lock: StoreLock,
+}
+
+impl JsonBackend {
+    pub fn open(filepath: &str) -> Result<JsonBackend, TodoError> {
+        // Hold the lock before reading anything so no other process can write underneath us
+        let lock = StoreLock::acquire(filepath)?;
+
+        Ok(JsonBackend {
+            filepath: String::from(filepath),
+            journal: Journal::new(filepath, 0),
+            _lock: lock,
+        })
+    }
+}
+
+impl StorageBackend for JsonBackend {
+    fn load(&mut self) -> Result<LoadedStore, TodoError> {
+        // Open and read persistence store file contents
+        let persistence_store = File::options()
+            .read(true)
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&self.filepath)
+            .map_err(|err| {
+                TodoError::new(String::from("Error opening persistence file."), Box::new(err))
+            })?;
+
+        let mut persistence_store_contents = String::new();
+        BufReader::new(persistence_store)
+            .read_to_string(&mut persistence_store_contents)
+            .map_err(|err| {
+                TodoError::new(String::from("Error reading persistence file."), Box::new(err))
+            })?;
+
+        // Deserialize persistence store. A freshly created file starts out as an empty store.
+        let mut migrated_from_version = None;
+        let store_dto: TodoStoreSerializable = if persistence_store_contents.trim().is_empty() {
+            TodoStoreSerializable {
+                schema_version: migration::CURRENT_SCHEMA_VERSION,
+                store: Vec::new(),
+                trash: Vec::new(),
+                projects: Vec::new(),
+                views: Vec::new(),
+                next_id: 0,
+                history: UndoHistory::default(),
+                last_journal_seq: 0,
+            }
+        } else {
+            let mut document: Value =
+                serde_json::from_str(&persistence_store_contents).map_err(|err| {
+                    TodoError::new(
+                        String::from("Error reading persistence file. Data is likely corrupted."),
+                        Box::new(err),
+                    )
+                })?;
+
+            // Upgrade files written by older versions before reading them
+            migrated_from_version = migration::migrate(&mut document)?;
+
+            serde_json::from_value(document).map_err(|err| {
+                TodoError::new(
+                    String::from("Error reading persistence file. Data is likely corrupted."),
+                    Box::new(err),
+                )
+            })?
+        };
+
+        if let Some(original_version) = migrated_from_version {
+            // Keep the original around in case the upgrade needs to be undone by hand
+            let backup_filepath = format!("{}.v{}.bak", self.filepath, original_version);
+            fs::write(&backup_filepath, &persistence_store_contents).map_err(|err| {
+                TodoError::new(
+                    format!("Error writing backup file {}.", backup_filepath),
+                    Box::new(err),
+                )
+            })?;
+        }
+
+        // Recover any actions that were journaled but never compacted into the snapshot
+        self.journal = Journal::new(&self.filepath, store_dto.last_journal_seq);
+        let replay = self.journal.replay()?;
+
+        Ok(LoadedStore {
+            needs_snapshot: !replay.actions.is_empty()
+                || replay.torn_tail
+                || migrated_from_version.is_some(),
+            pending_actions: replay.actions,
+            snapshot: StoreSnapshot {
+                items: store_dto.store,
+                trash: store_dto.trash,
+                projects: store_dto.projects,
+                views: store_dto.views,
+                next_id: store_dto.next_id,
+                history: store_dto.history,
+            },
+        })
+    }
+
+    fn apply_change(&mut self, change: &StoreChange) -> Result<(), TodoError> {
+        self.journal.append(change.action)
+    }
+
+    // Write the snapshot to a temporary file, flush it to disk and rename it over the original.
+    // A crash at any point leaves either the previous or the new snapshot, never a partial one.
+    // The journal is only cleared once the new snapshot is durable.
+    fn snapshot(&mut self, snapshot: &StoreSnapshot) -> Result<(), TodoError> {
+        let store_dto = TodoStoreSerializable {
+            schema_version: migration::CURRENT_SCHEMA_VERSION,
+            next_id: snapshot.next_id,
+            store: snapshot.items.clone(),
+            trash: snapshot.trash.clone(),
+            projects: snapshot.projects.clone(),
+            views: snapshot.views.clone(),
+            history: snapshot.history.clone(),
+            last_journal_seq: self.journal.last_seq(),
+        };
+
+        let store_dto_json = serde_json::to_string_pretty(&store_dto).map_err(|err| {
+            TodoError::new(String::from("Error serializing store."), Box::new(err))
+        })?;
+
+        let temp_filepath = format!("{}.tmp", self.filepath);
+        File::create(&temp_filepath)
+            .and_then(|mut persistence| {
+                persistence.write_all(store_dto_json.as_bytes())?;
+                persistence.sync_all()
+            })
+            .and_then(|_| fs::rename(&temp_filepath, &self.filepath))
+            .and_then(|_| sync_parent_dir(&self.filepath))
+            .map_err(|err| {
+                TodoError::new(String::from("Error writing persistence file."), Box::new(err))
+            })?;
+
+        self.journal.clear()
+    }
+
+    fn pending_change_count(&self) -> usize {
+        self.journal.entry_count()
+    }
+}
+
+// The rename is only durable once the directory entry itself has been flushed
+#[cfg(unix)]
+fn sync_parent_dir(filepath: &str) -> io::Result<()> {
+    let parent = Path::new(filepath)
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+
+    File::open(parent)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_filepath: &str) -> io::Result<()> {
+    Ok(())
+}
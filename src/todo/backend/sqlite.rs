@@ -0,0 +1,283 @@
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+
+use crate::err::TodoError;
+use crate::todo::action::UndoHistory;
+use crate::todo::backend::{LoadedStore, StorageBackend, StoreChange, StoreSnapshot};
+use crate::todo::lock::StoreLock;
+use crate::todo::{Project, SavedView, TodoItemSerializable};
+
+const SQLITE_SCHEMA_VERSION: i64 = 3;
+
+// SQLITE_MIGRATIONS[n] upgrades the tables from version n to version n + 1, where version 0 is an
+// empty database. Every item is one row. The columns used for lookups and ordering are indexed,
+// the full item is kept as JSON so new item fields never require a table change.
+const SQLITE_MIGRATIONS: [&str; SQLITE_SCHEMA_VERSION as usize] = [
+    "
+    CREATE TABLE items (
+        id INTEGER PRIMARY KEY,
+        due_date TEXT NOT NULL,
+        complete INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX items_due_date ON items (due_date);
+    CREATE INDEX items_complete_due_date ON items (complete, due_date);
+    CREATE TABLE meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    ",
+    "
+    ALTER TABLE items ADD COLUMN trashed INTEGER NOT NULL DEFAULT 0;
+    CREATE INDEX items_trashed ON items (trashed);
+    ",
+    // SQLite cannot drop a NOT NULL constraint, so the table is rebuilt to allow undated items
+    "
+    CREATE TABLE items_nullable_due_date (
+        id INTEGER PRIMARY KEY,
+        due_date TEXT,
+        complete INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        trashed INTEGER NOT NULL DEFAULT 0
+    );
+    INSERT INTO items_nullable_due_date (id, due_date, complete, data, trashed)
+        SELECT id, due_date, complete, data, trashed FROM items;
+    DROP TABLE items;
+    ALTER TABLE items_nullable_due_date RENAME TO items;
+    CREATE INDEX items_due_date ON items (due_date);
+    CREATE INDEX items_complete_due_date ON items (complete, due_date);
+    CREATE INDEX items_trashed ON items (trashed);
+    ",
+];
+
+// Persists each change in place, so there is never a journal to compact
+pub struct SqliteBackend {
+    connection: Connection,
+    _lock: StoreLock,
+}
+
+impl SqliteBackend {
+    pub fn open(filepath: &str) -> Result<SqliteBackend, TodoError> {
+        let lock = StoreLock::acquire(filepath)?;
+
+        let connection = Connection::open(filepath).map_err(|err| {
+            TodoError::new(format!("Error opening SQLite store {}.", filepath), Box::new(err))
+        })?;
+
+        let schema_version: i64 = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(sqlite_error)?;
+        if schema_version > SQLITE_SCHEMA_VERSION {
+            return Err(TodoError::new_from_msg(format!(
+                "SQLite store has schema version {} but this version of todo_assistant only supports up to {}.",
+                schema_version, SQLITE_SCHEMA_VERSION
+            )));
+        }
+
+        for (version, migration) in SQLITE_MIGRATIONS.iter().enumerate().skip(schema_version as usize) {
+            connection
+                .execute_batch(&format!(
+                    "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+                    migration,
+                    version + 1
+                ))
+                .map_err(sqlite_error)?;
+        }
+
+        Ok(SqliteBackend {
+            connection,
+            _lock: lock,
+        })
+    }
+
+    fn get_meta(&self, key: &str) -> Result<Option<String>, TodoError> {
+        self.connection
+            .query_row("SELECT value FROM meta WHERE key = ?1", params![key], |row| {
+                row.get::<_, String>(0)
+            })
+            .optional()
+            .map_err(sqlite_error)
+    }
+
+    fn load_items(&self, trashed: bool) -> Result<Vec<TodoItemSerializable>, TodoError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT data FROM items WHERE trashed = ?1 ORDER BY due_date, id")
+            .map_err(sqlite_error)?;
+
+        let items = statement
+            .query_map(params![trashed], |row| row.get::<_, String>(0))
+            .map_err(sqlite_error)?
+            .map(|data| {
+                let data = data.map_err(sqlite_error)?;
+                serde_json::from_str::<TodoItemSerializable>(&data).map_err(|err| {
+                    TodoError::new(
+                        String::from("Error reading SQLite store. Data is likely corrupted."),
+                        Box::new(err),
+                    )
+                })
+            })
+            .collect::<Result<Vec<TodoItemSerializable>, TodoError>>()?;
+
+        Ok(items)
+    }
+}
+
+impl StorageBackend for SqliteBackend {
+    fn load(&mut self) -> Result<LoadedStore, TodoError> {
+        let items = self.load_items(false)?;
+        let trash = self.load_items(true)?;
+
+        let next_id = self
+            .get_meta("next_id")?
+            .map(|next_id| next_id.parse::<usize>())
+            .transpose()
+            .map_err(|err| {
+                TodoError::new(
+                    String::from("Error reading SQLite store. next_id is not a number."),
+                    Box::new(err),
+                )
+            })?
+            .unwrap_or(0);
+        let history = self
+            .get_meta("undo_history")?
+            .map(|history| serde_json::from_str::<UndoHistory>(&history))
+            .transpose()
+            .map_err(|err| {
+                TodoError::new(
+                    String::from("Error reading SQLite store. Undo history is corrupted."),
+                    Box::new(err),
+                )
+            })?
+            .unwrap_or_default();
+        let projects = self
+            .get_meta("projects")?
+            .map(|projects| serde_json::from_str::<Vec<Project>>(&projects))
+            .transpose()
+            .map_err(|err| {
+                TodoError::new(
+                    String::from("Error reading SQLite store. Projects are corrupted."),
+                    Box::new(err),
+                )
+            })?
+            .unwrap_or_default();
+        let views = self
+            .get_meta("views")?
+            .map(|views| serde_json::from_str::<Vec<SavedView>>(&views))
+            .transpose()
+            .map_err(|err| {
+                TodoError::new(
+                    String::from("Error reading SQLite store. Saved views are corrupted."),
+                    Box::new(err),
+                )
+            })?
+            .unwrap_or_default();
+
+        Ok(LoadedStore {
+            snapshot: StoreSnapshot {
+                items,
+                trash,
+                projects,
+                views,
+                next_id,
+                history,
+            },
+            pending_actions: Vec::new(),
+            needs_snapshot: false,
+        })
+    }
+
+    fn apply_change(&mut self, change: &StoreChange) -> Result<(), TodoError> {
+        let transaction = self.connection.transaction().map_err(sqlite_error)?;
+
+        for item in &change.items {
+            upsert_item(&transaction, item, false)?;
+        }
+        for item in &change.trashed {
+            upsert_item(&transaction, item, true)?;
+        }
+        for id in &change.removed_ids {
+            transaction
+                .execute("DELETE FROM items WHERE id = ?1", params![*id as i64])
+                .map_err(sqlite_error)?;
+        }
+        set_meta(&transaction, "next_id", &change.next_id.to_string())?;
+        set_meta(&transaction, "undo_history", &serialize_history(change.history)?)?;
+        set_meta(&transaction, "projects", &serialize_projects(change.projects)?)?;
+        set_meta(&transaction, "views", &serialize_views(change.views)?)?;
+
+        transaction.commit().map_err(sqlite_error)
+    }
+
+    fn snapshot(&mut self, snapshot: &StoreSnapshot) -> Result<(), TodoError> {
+        let transaction = self.connection.transaction().map_err(sqlite_error)?;
+
+        transaction
+            .execute("DELETE FROM items", [])
+            .map_err(sqlite_error)?;
+        for item in &snapshot.items {
+            upsert_item(&transaction, item, false)?;
+        }
+        for item in &snapshot.trash {
+            upsert_item(&transaction, item, true)?;
+        }
+        set_meta(&transaction, "next_id", &snapshot.next_id.to_string())?;
+        set_meta(&transaction, "undo_history", &serialize_history(&snapshot.history)?)?;
+        set_meta(&transaction, "projects", &serialize_projects(&snapshot.projects)?)?;
+        set_meta(&transaction, "views", &serialize_views(&snapshot.views)?)?;
+
+        transaction.commit().map_err(sqlite_error)
+    }
+}
+
+fn upsert_item(
+    transaction: &Transaction,
+    item: &TodoItemSerializable,
+    trashed: bool,
+) -> Result<(), TodoError> {
+    let data = serde_json::to_string(item).map_err(|err| {
+        TodoError::new(String::from("Error serializing todo item."), Box::new(err))
+    })?;
+
+    transaction
+        .execute(
+            "INSERT OR REPLACE INTO items (id, due_date, complete, data, trashed)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![item.id as i64, item.due_date, item.complete, data, trashed],
+        )
+        .map_err(sqlite_error)?;
+
+    Ok(())
+}
+
+fn set_meta(transaction: &Transaction, key: &str, value: &str) -> Result<(), TodoError> {
+    transaction
+        .execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )
+        .map_err(sqlite_error)?;
+
+    Ok(())
+}
+
+fn serialize_history(history: &UndoHistory) -> Result<String, TodoError> {
+    serde_json::to_string(history).map_err(|err| {
+        TodoError::new(String::from("Error serializing undo history."), Box::new(err))
+    })
+}
+
+fn serialize_projects(projects: &[Project]) -> Result<String, TodoError> {
+    serde_json::to_string(projects).map_err(|err| {
+        TodoError::new(String::from("Error serializing projects."), Box::new(err))
+    })
+}
+
+fn serialize_views(views: &[SavedView]) -> Result<String, TodoError> {
+    serde_json::to_string(views).map_err(|err| {
+        TodoError::new(String::from("Error serializing saved views."), Box::new(err))
+    })
+}
+
+fn sqlite_error(err: rusqlite::Error) -> TodoError {
+    TodoError::new(String::from("Error accessing SQLite store."), Box::new(err))
+}
@@ -0,0 +1,483 @@
+use crate::err::TodoError;
+use chrono::{Datelike, Duration, FixedOffset, Local, NaiveDate, NaiveTime, Weekday};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+pub const ACCEPTED_FORMS: &str =
+    "YYYY-MM-DD, today, tomorrow, a weekday like fri, next monday, +3d, +2w, in 2 weeks or eom";
+
+pub const ACCEPTED_TIME_FORMS: &str =
+    "HH:MM or 3pm, optionally followed by a UTC offset like UTC or +02:00";
+
+// Longest due date in words, as in "in 2 weeks"
+const MAX_WORDS: usize = 3;
+
+// Units of "+3d" and "in 3 days"
+const OFFSET_UNITS: [&str; 9] = ["d", "day", "days", "w", "week", "weeks", "m", "month", "months"];
+
+const WEEKDAY_NAMES: [(Weekday, &[&str]); 7] = [
+    (Weekday::Mon, &["mon", "monday"]),
+    (Weekday::Tue, &["tue", "tues", "tuesday"]),
+    (Weekday::Wed, &["wed", "wednesday"]),
+    (Weekday::Thu, &["thu", "thur", "thurs", "thursday"]),
+    (Weekday::Fri, &["fri", "friday"]),
+    (Weekday::Sat, &["sat", "saturday"]),
+    (Weekday::Sun, &["sun", "sunday"]),
+];
+
+// Time of day an item is due by, optionally in a fixed time zone. Times without a zone are in
+// the local time zone of whoever is looking at the item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DueTime {
+    pub time: NaiveTime,
+    pub zone: Option<FixedOffset>,
+}
+
+impl DueTime {
+    // Parse "15:00", "3pm" or "3:30pm", optionally followed by "UTC" or an offset like "+02:00"
+    pub fn parse(input: &str) -> Result<DueTime, TodoError> {
+        let words: Vec<&str> = input.split_whitespace().collect();
+
+        match DueTime::parse_leading(&words) {
+            Some((due_time, length)) if length == words.len() => Ok(due_time),
+            _ => Err(TodoError::new_from_msg(format!(
+                "Unrecognized due time '{}'. Expected {}.",
+                input.trim(),
+                ACCEPTED_TIME_FORMS
+            ))),
+        }
+    }
+
+    // Like parse, but "none" removes the time
+    pub fn parse_optional(input: &str) -> Result<Option<DueTime>, TodoError> {
+        match input.trim().to_lowercase().as_str() {
+            "none" => Ok(None),
+            _ => DueTime::parse(input).map(Some),
+        }
+    }
+
+    // A due time at the start of the given words, and how many words it took up
+    fn parse_leading(words: &[&str]) -> Option<(DueTime, usize)> {
+        let time = parse_time(words.first()?)?;
+
+        match words.get(1).and_then(|word| parse_zone(word)) {
+            Some(zone) => Some((DueTime { time, zone: Some(zone) }, 2)),
+            None => Some((DueTime { time, zone: None }, 1)),
+        }
+    }
+}
+
+impl fmt::Display for DueTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.time.format("%H:%M"))?;
+        match self.zone {
+            Some(zone) if zone.local_minus_utc() == 0 => write!(f, " UTC"),
+            Some(zone) => write!(f, " {}", zone),
+            None => Ok(()),
+        }
+    }
+}
+
+// Persisted in the same form it is displayed in, such as "15:00 +02:00"
+impl Serialize for DueTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for DueTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let due_time = String::deserialize(deserializer)?;
+
+        DueTime::parse(&due_time).map_err(|err| serde::de::Error::custom(err.message))
+    }
+}
+
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+// A due date and optional due time at the start of the given words, such as "next monday 3pm",
+// along with how many words they took up
+pub fn parse_leading(
+    words: &[&str],
+    today: NaiveDate,
+) -> Result<(NaiveDate, Option<DueTime>, usize), TodoError> {
+    // Relative dates can take several words, so try the longest date first. The error for the
+    // single first word is the most helpful one if none of them are dates.
+    for date_length in (1..=MAX_WORDS.min(words.len())).rev() {
+        match parse(&words[..date_length].join(" "), today) {
+            Ok(due_date) => {
+                return Ok(match DueTime::parse_leading(&words[date_length..]) {
+                    Some((due_time, time_length)) => {
+                        (due_date, Some(due_time), date_length + time_length)
+                    }
+                    None => (due_date, None, date_length),
+                })
+            }
+            Err(err) if date_length == 1 => return Err(err),
+            Err(_) => {}
+        }
+    }
+
+    Err(TodoError::new_from_msg(format!(
+        "A due date is required. Expected {}.",
+        ACCEPTED_FORMS
+    )))
+}
+
+// Like parse_leading, but words that do not start with a due date give no due date at all.
+// Words shaped like a date, such as YYYY-MM-DD, "+3d", "in 3 days" or "next fri", are still taken
+// as one so typos and out of range dates in them are reported. A weekday on its own reads just as
+// well as the start of a title, as in "Sun cream" or "Friday drinks", so it is only a date when a
+// due time follows it. "on fri" and "next fri" always are.
+pub fn parse_optional_leading(
+    words: &[&str],
+    today: NaiveDate,
+) -> Result<(Option<NaiveDate>, Option<DueTime>, usize), TodoError> {
+    match parse_leading(words, today) {
+        Ok((_, None, 1)) if parse_weekday(words[0]).is_some() => Ok((None, None, 0)),
+        Ok((due_date, due_time, length)) => Ok((Some(due_date), due_time, length)),
+        Err(err) => match date_like_length(words) {
+            0 => Ok((None, None, 0)),
+            length => Err(parse(&words[..length].join(" "), today).err().unwrap_or(err)),
+        },
+    }
+}
+
+// How many of the leading words are shaped like a due date, or 0 if they are not
+fn date_like_length(words: &[&str]) -> usize {
+    let iso_like_regex = Regex::new(r"^\d{4}-\d+-\d+$").unwrap();
+    let offset_regex = Regex::new(r"^\+\d").unwrap();
+    let count_regex = Regex::new(r"^\d+$").unwrap();
+    let words: Vec<String> = words.iter().map(|word| word.to_lowercase()).collect();
+
+    match words.as_slice() {
+        [first, ..] if iso_like_regex.is_match(first) || offset_regex.is_match(first) => 1,
+        [first, count, unit, ..]
+            if first == "in"
+                && count_regex.is_match(count)
+                && OFFSET_UNITS.contains(&unit.as_str()) =>
+        {
+            3
+        }
+        [first, _, ..] if first == "next" => 2,
+        _ => 0,
+    }
+}
+
+// Parse a due date that may be followed by a due time
+pub fn parse_with_time(
+    input: &str,
+    today: NaiveDate,
+) -> Result<(NaiveDate, Option<DueTime>), TodoError> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    let (due_date, due_time, length) = parse_leading(&words, today)?;
+
+    if length < words.len() {
+        return Err(TodoError::new_from_msg(format!(
+            "Unrecognized due time '{}'. Expected {}.",
+            words[length..].join(" "),
+            ACCEPTED_TIME_FORMS
+        )));
+    }
+
+    Ok((due_date, due_time))
+}
+
+// Parse a due date written either as YYYY-MM-DD or relative to today:
+// - "today" and "tomorrow"
+// - a weekday such as "fri" or "on fri", meaning the first one after today
+// - "next" and a weekday, meaning that weekday in the week after this one (weeks start on Monday)
+// - "+3d", "+2w" or "+1m" and "in 3 days", "in 2 weeks" or "in 1 month"
+// - "eom" for the last day of this month
+pub fn parse(input: &str, today: NaiveDate) -> Result<NaiveDate, TodoError> {
+    let original_input = input.trim();
+    let input = original_input.to_lowercase();
+    let words: Vec<&str> = input.split_whitespace().collect();
+
+    let iso_regex = Regex::new(r"^\d{4}-\d{2}-\d{2}$").unwrap();
+    if iso_regex.is_match(&input) {
+        return NaiveDate::parse_from_str(&input, "%Y-%m-%d").map_err(|err| {
+            TodoError::new(
+                String::from("Due date must be a valid date in the format YYYY-MM-DD."),
+                Box::new(err),
+            )
+        });
+    }
+
+    let due_date = match words.as_slice() {
+        ["today"] => Some(today),
+        ["tomorrow"] => add_days(today, 1),
+        ["eom"] => day_in_month(today.year(), today.month(), 31),
+        [offset] if offset.starts_with('+') => parse_offset(&offset[1..], today),
+        [weekday] | ["on", weekday] => parse_weekday(weekday).and_then(|weekday| {
+            let days_ahead = (weekday.num_days_from_monday() + 6
+                - today.weekday().num_days_from_monday())
+                % 7
+                + 1;
+            add_days(today, days_ahead as i64)
+        }),
+        ["next", weekday] => parse_weekday(weekday).and_then(|weekday| {
+            let days_to_monday = 7 - today.weekday().num_days_from_monday();
+            add_days(today, (days_to_monday + weekday.num_days_from_monday()) as i64)
+        }),
+        ["in", count, unit] => parse_offset(&format!("{}{}", count, unit), today),
+        _ => None,
+    };
+
+    due_date.ok_or_else(|| {
+        TodoError::new_from_msg(format!(
+            "Unrecognized due date '{}'. Expected {}.",
+            original_input, ACCEPTED_FORMS
+        ))
+    })
+}
+
+// Weekdays by their full or abbreviated English name
+pub fn parse_weekday(input: &str) -> Option<Weekday> {
+    let input = input.trim().to_lowercase();
+
+    WEEKDAY_NAMES
+        .iter()
+        .find(|(_, names)| names.contains(&input.as_str()))
+        .map(|(weekday, _)| *weekday)
+}
+
+// The given day of a month, moved back to the last day of the month if the month is shorter. None
+// if the year is out of range.
+pub fn day_in_month(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    (1..=day)
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+}
+
+// The same day a number of months later, or the last day of that month if it is shorter
+pub fn add_months(date: NaiveDate, months: u32) -> Option<NaiveDate> {
+    let month_index = date.month0().checked_add(months)?;
+    let year = date.year().checked_add(i32::try_from(month_index / 12).ok()?)?;
+
+    day_in_month(year, month_index % 12 + 1, date.day())
+}
+
+// None if the result is out of range
+pub fn add_days(date: NaiveDate, days: i64) -> Option<NaiveDate> {
+    date.checked_add_signed(Duration::days(days))
+}
+
+// Times such as "15:00", "3pm" or "3:30pm"
+fn parse_time(input: &str) -> Option<NaiveTime> {
+    let input = input.to_lowercase();
+    let regex = Regex::new(r"^(\d{1,2})(?::(\d{2}))?(am|pm)?$").unwrap();
+    let captures = regex.captures(&input)?;
+
+    let mut hour = captures[1].parse::<u32>().ok()?;
+    let minute = captures.get(2).map_or(Some(0), |minute| minute.as_str().parse::<u32>().ok())?;
+    match captures.get(3).map(|suffix| suffix.as_str()) {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some("am") => hour %= 12,
+        Some(_) => hour = hour % 12 + 12,
+        // A bare number is too easily part of the title
+        None if captures.get(2).is_none() => return None,
+        None => {}
+    }
+
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+// Time zones as UTC offsets: "UTC", "Z", "+02:00", "-0530" or "+2"
+fn parse_zone(input: &str) -> Option<FixedOffset> {
+    let input = input.to_lowercase();
+    if input == "utc" || input == "z" {
+        return FixedOffset::east_opt(0);
+    }
+
+    let regex = Regex::new(r"^([+-])(\d{1,2})(?::?(\d{2}))?$").unwrap();
+    let captures = regex.captures(&input)?;
+    let hours = captures[2].parse::<i32>().ok()?;
+    let minutes = captures.get(3).map_or(Some(0), |minutes| minutes.as_str().parse::<i32>().ok())?;
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    let seconds = (hours * 60 + minutes) * 60;
+
+    match &captures[1] {
+        "-" => FixedOffset::west_opt(seconds),
+        _ => FixedOffset::east_opt(seconds),
+    }
+}
+
+// Offsets such as "3d", "2weeks" or "1month"
+fn parse_offset(input: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let unit_start = input.find(|c: char| !c.is_ascii_digit())?;
+    let (count, unit) = input.split_at(unit_start);
+    let count = count.parse::<u32>().ok()?;
+
+    match unit {
+        "d" | "day" | "days" => add_days(today, count as i64),
+        "w" | "week" | "weeks" => add_days(today, count as i64 * 7),
+        "m" | "month" | "months" => add_months(today, count),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod due_date_tests {
+    use super::*;
+
+    // A Wednesday
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 1, 18).unwrap()
+    }
+
+    fn parse_relative(input: &str) -> NaiveDate {
+        parse(input, today()).unwrap()
+    }
+
+    #[test]
+    fn iso_dates() {
+        assert_eq!(NaiveDate::from_ymd_opt(2022, 12, 1).unwrap(), parse_relative("2022-12-01"));
+
+        let error = parse("2023-02-30", today()).err().unwrap();
+        assert_eq!(
+            "Due date must be a valid date in the format YYYY-MM-DD.",
+            &error.message
+        );
+    }
+
+    #[test]
+    fn relative_dates() {
+        assert_eq!(today(), parse_relative("Today"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 1, 19).unwrap(), parse_relative("tomorrow"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 1, 21).unwrap(), parse_relative("+3d"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 2, 1).unwrap(), parse_relative("+2w"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 2, 1).unwrap(), parse_relative("in 2 weeks"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 1, 19).unwrap(), parse_relative("in 1 day"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 2, 18).unwrap(), parse_relative("in 1 month"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 1, 31).unwrap(), parse_relative("eom"));
+    }
+
+    #[test]
+    fn weekdays() {
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 1, 20).unwrap(), parse_relative("fri"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 1, 20).unwrap(), parse_relative("on Friday"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 1, 23).unwrap(), parse_relative("Monday"));
+        // The same weekday as today is a week away
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 1, 25).unwrap(), parse_relative("wed"));
+
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 1, 23).unwrap(), parse_relative("next monday"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 1, 27).unwrap(), parse_relative("next fri"));
+        let sunday = NaiveDate::from_ymd_opt(2023, 1, 22).unwrap();
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2023, 1, 23).unwrap(),
+            parse("next mon", sunday).unwrap()
+        );
+    }
+
+    #[test]
+    fn due_times() {
+        let due_time = DueTime::parse("3:30pm").unwrap();
+        assert_eq!(NaiveTime::from_hms_opt(15, 30, 0).unwrap(), due_time.time);
+        assert_eq!(None, due_time.zone);
+        assert_eq!("15:30", due_time.to_string());
+
+        assert_eq!("00:00 UTC", DueTime::parse("12am utc").unwrap().to_string());
+        assert_eq!("09:05 -05:30", DueTime::parse("09:05 -0530").unwrap().to_string());
+        assert_eq!("17:00 +02:00", DueTime::parse("5pm +2").unwrap().to_string());
+        assert_eq!(None, DueTime::parse_optional("none").unwrap());
+
+        for input in ["15", "25:00", "13pm", "15:00 Mars", "15:00 +15"] {
+            let error = DueTime::parse(input).err().unwrap();
+            assert!(error.message.starts_with("Unrecognized due time"), "{}", input);
+        }
+    }
+
+    #[test]
+    fn dates_with_times() {
+        let words = ["tomorrow", "9am", "UTC", "Standup"];
+        let (due_date, due_time, length) = parse_leading(&words, today()).unwrap();
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 1, 19).unwrap(), due_date);
+        assert_eq!("09:00 UTC", due_time.unwrap().to_string());
+        assert_eq!(3, length);
+
+        let (due_date, due_time) = parse_with_time("in 2 days", today()).unwrap();
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 1, 20).unwrap(), due_date);
+        assert_eq!(None, due_time);
+        assert!(parse_with_time("today 15:00 later", today()).is_err());
+    }
+
+    #[test]
+    fn optional_dates() {
+        let words = ["Someday", "learn", "Rust"];
+        assert_eq!((None, None, 0), parse_optional_leading(&words, today()).unwrap());
+
+        let words = ["on", "fri", "Deploy"];
+        let (due_date, _, length) = parse_optional_leading(&words, today()).unwrap();
+        assert_eq!(Some(NaiveDate::from_ymd_opt(2023, 1, 20).unwrap()), due_date);
+        assert_eq!(2, length);
+
+        // A weekday on its own is the start of the title unless a time follows it
+        for words in [["Sun", "cream"], ["Friday", "drinks"], ["fri", "Deploy"]] {
+            assert_eq!((None, None, 0), parse_optional_leading(&words, today()).unwrap());
+        }
+        let words = ["fri", "3pm", "Deploy"];
+        let (due_date, due_time, length) = parse_optional_leading(&words, today()).unwrap();
+        assert_eq!(Some(NaiveDate::from_ymd_opt(2023, 1, 20).unwrap()), due_date);
+        assert_eq!("15:00", due_time.unwrap().to_string());
+        assert_eq!(2, length);
+
+        assert!(parse_optional_leading(&["2023-1-1", "Deploy"], today()).is_err());
+        assert!(parse_optional_leading(&["2023-02-30", "Deploy"], today()).is_err());
+
+        // Words shaped like a date are reported instead of becoming the title
+        for (input, date) in [
+            ("+99999999999d x", "+99999999999d"),
+            ("+3x Deploy", "+3x"),
+            ("in 99999999 weeks x", "in 99999999 weeks"),
+            ("next fryday Deploy", "next fryday"),
+        ] {
+            let words: Vec<&str> = input.split_whitespace().collect();
+            let error = parse_optional_leading(&words, today()).err().unwrap();
+            assert!(
+                error.message.starts_with(&format!("Unrecognized due date '{}'.", date)),
+                "{}",
+                error.message
+            );
+        }
+        let words = ["in", "the", "garden"];
+        assert_eq!((None, None, 0), parse_optional_leading(&words, today()).unwrap());
+        let words = ["In", "3", "acts"];
+        assert_eq!((None, None, 0), parse_optional_leading(&words, today()).unwrap());
+    }
+
+    #[test]
+    fn months_keep_the_day_where_possible() {
+        assert_eq!(
+            Some(NaiveDate::from_ymd_opt(2023, 2, 28).unwrap()),
+            add_months(NaiveDate::from_ymd_opt(2023, 1, 31).unwrap(), 1)
+        );
+        assert_eq!(
+            Some(NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()),
+            add_months(NaiveDate::from_ymd_opt(2023, 11, 15).unwrap(), 2)
+        );
+    }
+
+    #[test]
+    fn out_of_range_offsets() {
+        for input in ["+4000000000d", "+99999999m", "in 4000000000 weeks", "+4294967295m"] {
+            let error = parse(input, today()).err().unwrap();
+            assert!(error.message.starts_with("Unrecognized due date"), "{}", input);
+        }
+        assert_eq!(None, add_months(NaiveDate::from_ymd_opt(2023, 1, 31).unwrap(), u32::MAX));
+    }
+
+    #[test]
+    fn unrecognized() {
+        for input in ["someday", "+3x", "next", "in two weeks", "2023-1-1"] {
+            let error = parse(input, today()).err().unwrap();
+            assert!(error.message.starts_with("Unrecognized due date"), "{}", input);
+            assert!(error.message.ends_with(&format!("Expected {}.", ACCEPTED_FORMS)));
+        }
+    }
+}
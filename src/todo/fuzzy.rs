@@ -0,0 +1,145 @@
+// Case-insensitive fuzzy matching of search text against item titles. Every word of the search
+// text has to appear in the title with its letters in order, though not necessarily next to each
+// other, so "dpl api" finds "Deploy api".
+
+const MATCH_SCORE: i64 = 10;
+const WORD_START_BONUS: i64 = 15;
+const CONSECUTIVE_BONUS: i64 = 10;
+const GAP_PENALTY: i64 = 2;
+// Matches further into the title rank lower, up to this many points
+const MAX_OFFSET_PENALTY: i64 = 10;
+
+const HIGHLIGHT_START: &str = "\x1b[1;4m";
+const HIGHLIGHT_END: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    // Higher is better
+    pub score: i64,
+    // Indices of the matched characters in the text, in order
+    pub positions: Vec<usize>,
+}
+
+// None when some word of the search text does not match, or there are no words at all
+pub fn fuzzy_match(search: &str, text: &str) -> Option<FuzzyMatch> {
+    let text: Vec<char> = text.chars().map(lowercase).collect();
+    let mut score = 0;
+    let mut positions = Vec::new();
+
+    for word in search.split_whitespace() {
+        let word: Vec<char> = word.chars().map(lowercase).collect();
+        let (word_score, word_positions) = best_word_match(&word, &text)?;
+        score += word_score;
+        positions.extend(word_positions);
+    }
+    if positions.is_empty() {
+        return None;
+    }
+    positions.sort_unstable();
+    positions.dedup();
+
+    Some(FuzzyMatch { score, positions })
+}
+
+// Wrap the characters at the given positions in terminal bold and underline
+pub fn highlight(text: &str, positions: &[usize]) -> String {
+    let mut highlighted = String::new();
+    let mut in_match = false;
+
+    for (index, c) in text.chars().enumerate() {
+        let matched = positions.contains(&index);
+        if matched && !in_match {
+            highlighted.push_str(HIGHLIGHT_START);
+        } else if !matched && in_match {
+            highlighted.push_str(HIGHLIGHT_END);
+        }
+        in_match = matched;
+        highlighted.push(c);
+    }
+    if in_match {
+        highlighted.push_str(HIGHLIGHT_END);
+    }
+
+    highlighted
+}
+
+// Try every place the word could start and keep the best scoring one. From each start the rest
+// of the word takes the earliest characters that fit.
+fn best_word_match(word: &[char], text: &[char]) -> Option<(i64, Vec<usize>)> {
+    let first = *word.first()?;
+
+    text.iter()
+        .enumerate()
+        .filter(|(_, c)| **c == first)
+        .filter_map(|(start, _)| {
+            let mut positions = vec![start];
+            for c in &word[1..] {
+                let previous = *positions.last().unwrap();
+                let next = text[previous + 1..].iter().position(|candidate| candidate == c)?;
+                positions.push(previous + 1 + next);
+            }
+
+            Some((score_positions(&positions, text), positions))
+        })
+        .max_by_key(|(score, positions)| (*score, std::cmp::Reverse(positions[0])))
+}
+
+fn score_positions(positions: &[usize], text: &[char]) -> i64 {
+    let mut score = -(positions[0] as i64).min(MAX_OFFSET_PENALTY);
+
+    for (i, position) in positions.iter().enumerate() {
+        score += MATCH_SCORE;
+        if *position == 0 || !text[position - 1].is_alphanumeric() {
+            score += WORD_START_BONUS;
+        }
+        if i > 0 {
+            let gap = (position - positions[i - 1] - 1) as i64;
+            match gap {
+                0 => score += CONSECUTIVE_BONUS,
+                gap => score -= gap * GAP_PENALTY,
+            }
+        }
+    }
+
+    score
+}
+
+// One character in, one character out, so positions line up with the original text
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod fuzzy_tests {
+    use super::*;
+
+    #[test]
+    fn matches_in_order() {
+        let result = fuzzy_match("dpl API", "Deploy api").unwrap();
+        assert_eq!(vec![0, 2, 3, 7, 8, 9], result.positions);
+
+        assert_eq!(None, fuzzy_match("lpd", "Deploy"));
+        assert_eq!(None, fuzzy_match("deploy web", "Deploy api"));
+        assert_eq!(None, fuzzy_match("  ", "Deploy api"));
+        assert!(fuzzy_match("ÉTÉ", "Café en été").is_some());
+    }
+
+    #[test]
+    fn ranking() {
+        let score = |search: &str, text: &str| fuzzy_match(search, text).unwrap().score;
+
+        // Whole words beat scattered letters, and earlier matches beat later ones
+        assert!(score("api", "Deploy api") > score("api", "Apply patch in"));
+        assert!(score("tea", "Tea with Ann") > score("tea", "Buy tea"));
+        assert!(score("rep", "Weekly report") > score("rep", "Water the plants"));
+    }
+
+    #[test]
+    fn highlights_runs() {
+        assert_eq!(
+            "\x1b[1;4mDe\x1b[0mploy \x1b[1;4mapi\x1b[0m",
+            highlight("Deploy api", &[0, 1, 7, 8, 9])
+        );
+        assert_eq!("Deploy", highlight("Deploy", &[]));
+    }
+}
@@ -1,63 +1,640 @@
 use crate::err::TodoError;
-use chrono::NaiveDate;
+use crate::todo::due_date::{self, DueTime};
+use crate::todo::project::Project;
+use crate::todo::recurrence::Recurrence;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use std::fmt;
 
-#[derive(Debug, Serialize, Deserialize)]
+// Longest title, in characters, unless the store is configured otherwise
+pub const DEFAULT_MAX_TITLE_LENGTH: usize = 50;
+const TAG_PATTERN: &str = r"[a-z0-9-_]{1,30}";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TodoItemSerializable {
     pub id: usize,
     pub title: String,
-    pub due_date: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_time: Option<DueTime>,
     pub complete: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub blocked_by: BTreeSet<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurs_from: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+}
+
+impl Priority {
+    pub fn parse(input: &str) -> Result<Priority, TodoError> {
+        match input.trim().to_lowercase().as_str() {
+            "high" | "h" => Ok(Priority::High),
+            "medium" | "med" | "m" => Ok(Priority::Medium),
+            "low" | "l" => Ok(Priority::Low),
+            other => Err(TodoError::new_from_msg(format!(
+                "Unknown priority '{}'. Expected high, medium or low.",
+                other
+            ))),
+        }
+    }
+
+    // Like parse, but "none" clears the priority
+    pub fn parse_optional(input: &str) -> Result<Option<Priority>, TodoError> {
+        match input.trim().to_lowercase().as_str() {
+            "none" => Ok(None),
+            other => Priority::parse(other).map(Some),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::High => "high",
+            Priority::Medium => "medium",
+            Priority::Low => "low",
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// The moments recorded on every item, which can be shown as extra table columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timestamp {
+    Created,
+    Completed,
+    Updated,
+}
+
+impl Timestamp {
+    pub fn parse(input: &str) -> Result<Timestamp, TodoError> {
+        match input.trim().to_lowercase().as_str() {
+            "created" => Ok(Timestamp::Created),
+            "completed" => Ok(Timestamp::Completed),
+            "updated" => Ok(Timestamp::Updated),
+            other => Err(TodoError::new_from_msg(format!(
+                "Unknown timestamp '{}'. Expected created, completed or updated.",
+                other
+            ))),
+        }
+    }
+
+    // Parses a list such as "created,updated". "none" hides every timestamp.
+    pub fn parse_list(input: &str) -> Result<Vec<Timestamp>, TodoError> {
+        if input.trim().eq_ignore_ascii_case("none") {
+            return Ok(Vec::new());
+        }
+
+        let mut timestamps = Vec::new();
+        for timestamp in input.split(|c: char| c == ',' || c.is_whitespace()) {
+            if timestamp.is_empty() {
+                continue;
+            }
+            let timestamp = Timestamp::parse(timestamp)?;
+            if !timestamps.contains(&timestamp) {
+                timestamps.push(timestamp);
+            }
+        }
+
+        Ok(timestamps)
+    }
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            Timestamp::Created => "Created",
+            Timestamp::Completed => "Completed",
+            Timestamp::Updated => "Updated",
+        }
+    }
 }
 
-// TODO: Maybe add date_completed
+// Everything a user can give when creating an item
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemInput {
+    pub due_date: Option<NaiveDate>,
+    pub due_time: Option<DueTime>,
+    pub title: String,
+    pub priority: Option<Priority>,
+    pub tags: BTreeSet<String>,
+    pub project: Option<String>,
+    // ID of the item this one is a subtask of
+    pub parent: Option<usize>,
+    // IDs of the items that have to be completed before this one
+    pub blocked_by: BTreeSet<usize>,
+    pub recurrence: Option<Recurrence>,
+}
+
+impl ItemInput {
+    pub fn new(due_date: NaiveDate, title: &str) -> ItemInput {
+        ItemInput {
+            due_date: Some(due_date),
+            due_time: None,
+            title: String::from(title),
+            priority: None,
+            tags: BTreeSet::new(),
+            project: None,
+            parent: None,
+            blocked_by: BTreeSet::new(),
+            recurrence: None,
+        }
+    }
+}
+
+// Fields to change on an existing item. Fields left as None keep their current value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ItemChanges {
+    pub title: Option<String>,
+    // Some(None) removes the due date, and with it the due time
+    pub due_date: Option<Option<NaiveDate>>,
+    // Some(None) leaves the item due at any time of its due date
+    pub due_time: Option<Option<DueTime>>,
+    // Some(None) clears the priority
+    pub priority: Option<Option<Priority>>,
+    // Replaces every tag on the item
+    pub tags: Option<BTreeSet<String>>,
+    // Some(None) takes the item out of its project
+    pub project: Option<Option<String>>,
+    // Some(None) turns a subtask into a top level item
+    pub parent: Option<Option<usize>>,
+    // Replaces every blocker of the item
+    pub blocked_by: Option<BTreeSet<usize>>,
+    // Some(None) stops the item from repeating
+    pub recurrence: Option<Option<Recurrence>>,
+}
+
+impl ItemChanges {
+    pub fn is_empty(&self) -> bool {
+        *self == ItemChanges::default()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct TodoItem {
     pub id: usize,
     pub title: String,
-    pub due_date: NaiveDate,
+    pub due_date: Option<NaiveDate>,
+    pub due_time: Option<DueTime>,
     pub complete: bool,
+    pub priority: Option<Priority>,
+    pub tags: BTreeSet<String>,
+    pub project: Option<String>,
+    pub parent: Option<usize>,
+    pub blocked_by: BTreeSet<usize>,
+    pub recurrence: Option<Recurrence>,
+    // The completed occurrence of a recurring item that this one was scheduled from
+    pub recurs_from: Option<usize>,
+    // None for items saved before timestamps were recorded
+    pub created_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
 }
 
 impl TodoItem {
     pub fn new(args: String, id: usize) -> Result<TodoItem, TodoError> {
-        let regex_pattern = r"^(\d{4}-[0-1]\d-[0-3]\d)\s([A-Za-z0-9-_?.<> ]{1,50})$";
-        let regex = Regex::new(regex_pattern).unwrap();
+        let input = TodoItem::parse_input(&args)?;
+        TodoItem::validate_title_length(&input.title, DEFAULT_MAX_TITLE_LENGTH)?;
 
-        if !regex.is_match(&args.trim()) {
-            return Err(TodoError::new_from_msg(String::from(
-                "Invalid format for new Todo item.",
-            )));
-        }
+        TodoItem::from_input(id, input)
+    }
 
-        let captures = regex.captures(&args.trim()).unwrap();
-        let due_date = &captures[1];
-        let title = String::from(&captures[2]);
+    pub fn from_input(id: usize, input: ItemInput) -> Result<TodoItem, TodoError> {
+        let mut item = TodoItem::from_parts(id, input.due_date, input.title)?;
+        item.due_time = input.due_time;
+        item.priority = input.priority;
+        item.tags = input.tags;
+        item.project = input.project;
+        item.parent = input.parent;
+        item.blocked_by = input.blocked_by;
+        item.recurrence = match (input.recurrence, item.due_date) {
+            (Some(recurrence), Some(due_date)) => Some(recurrence.pinned_to(due_date)),
+            (recurrence, _) => recurrence,
+        };
 
-        let due_date = NaiveDate::parse_from_str(due_date, "%Y-%m-%d").unwrap();
+        Ok(item)
+    }
+
+    pub fn from_parts(id: usize, due_date: Option<NaiveDate>, title: String) -> Result<TodoItem, TodoError> {
+        TodoItem::validate_title(&title)?;
+        let now = Utc::now();
 
         Ok(TodoItem {
             id,
             title,
             due_date,
+            due_time: None,
             complete: false,
+            priority: None,
+            tags: BTreeSet::new(),
+            project: None,
+            parent: None,
+            blocked_by: BTreeSet::new(),
+            recurrence: None,
+            recurs_from: None,
+            created_at: Some(now),
+            completed_at: None,
+            updated_at: Some(now),
         })
     }
 
+    // Splits user input in the form "[Due date [Due time]] {Title}" into its parts. The due date is
+    // either YYYY-MM-DD or relative to today, such as "tomorrow" or "in 2 weeks", and the optional
+    // time looks like "15:00" or "3pm UTC". Input that does not start with a date is all title.
+    // Words after the first one such as "!high" set the priority, "#ops" adds a tag, "@work" picks
+    // the project, "^3" makes the item a subtask of item 3 and "*weekly" makes it repeat. Words
+    // that are not valid markers, such as "!!" or "#1", stay in the title, and a leading backslash
+    // keeps any word as it is, as in "\#ops".
+    pub fn parse_input(args: &str) -> Result<ItemInput, TodoError> {
+        TodoItem::parse_input_relative_to(args, due_date::today())
+    }
+
+    pub fn parse_input_relative_to(args: &str, today: NaiveDate) -> Result<ItemInput, TodoError> {
+        TodoItem::parse_input_with_projects(args, today, |_| true)
+    }
+
+    // Like parse_input_relative_to, but "@name" only picks a project when is_project says it
+    // exists, so "Email @bob" keeps "@bob" in the title
+    pub fn parse_input_with_projects<F>(
+        args: &str,
+        today: NaiveDate,
+        is_project: F,
+    ) -> Result<ItemInput, TodoError>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut priority = None;
+        let mut tags = BTreeSet::new();
+        let mut project = None;
+        let mut parent = None;
+        let mut recurrence = None;
+        let mut words = Vec::new();
+        // An escaped first word is never read as a due date
+        let mut first_word_escaped = false;
+        for word in args.split_whitespace() {
+            if let Some(escaped) = word.strip_prefix('\\').filter(|escaped| !escaped.is_empty()) {
+                first_word_escaped |= words.is_empty();
+                words.push(escaped);
+            } else if words.is_empty() {
+                words.push(word);
+            } else if let Some(marker) = word
+                .strip_prefix('!')
+                .and_then(|marker| Priority::parse(marker).ok())
+            {
+                priority = Some(marker);
+            } else if let Some(tag) = word
+                .strip_prefix('#')
+                .filter(|tag| !tag.chars().all(|c| c.is_ascii_digit()))
+                .and_then(|tag| TodoItem::parse_tag(tag).ok())
+            {
+                tags.insert(tag);
+            } else if let Some(name) = word
+                .strip_prefix('@')
+                .and_then(|name| Project::parse_name(name).ok())
+                .filter(|name| is_project(name))
+            {
+                project = Some(name);
+            } else if let Some(id) = word
+                .strip_prefix('^')
+                .and_then(|id| TodoItem::parse_parent_id(id).ok())
+            {
+                parent = Some(id);
+            } else if let Some(rule) = word
+                .strip_prefix('*')
+                .and_then(|_| Recurrence::parse(word).ok())
+            {
+                recurrence = Some(rule);
+            } else {
+                words.push(word);
+            }
+        }
+
+        if words.is_empty() {
+            return Err(TodoError::new_from_msg(String::from(
+                "Invalid format for new Todo item.",
+            )));
+        }
+        let (due_date, due_time, due_length) = match first_word_escaped {
+            true => (None, None, 0),
+            false => due_date::parse_optional_leading(&words, today)?,
+        };
+
+        let title = words[due_length..].join(" ");
+        if title.is_empty() {
+            return Err(TodoError::new_from_msg(String::from(
+                "Invalid format for new Todo item.",
+            )));
+        }
+
+        Ok(ItemInput {
+            due_date,
+            due_time,
+            title,
+            priority,
+            tags,
+            project,
+            parent,
+            blocked_by: BTreeSet::new(),
+            recurrence,
+        })
+    }
+
+    pub fn parse_parent_id(input: &str) -> Result<usize, TodoError> {
+        input.trim().parse::<usize>().map_err(|err| {
+            TodoError::new(
+                format!("Invalid parent '{}'. The parent must be an item ID.", input.trim()),
+                Box::new(err),
+            )
+        })
+    }
+
+    // Parse a space or comma separated list of blocking item IDs. "none" gives an empty list.
+    pub fn parse_blockers(input: &str) -> Result<BTreeSet<usize>, TodoError> {
+        if input.trim().eq_ignore_ascii_case("none") {
+            return Ok(BTreeSet::new());
+        }
+
+        input
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|id| !id.is_empty())
+            .map(|id| {
+                id.parse::<usize>().map_err(|err| {
+                    TodoError::new(
+                        format!("Invalid blocker '{}'. Blockers must be item IDs.", id),
+                        Box::new(err),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    // Normalize a tag as typed by the user, with or without its leading '#'
+    pub fn parse_tag(input: &str) -> Result<String, TodoError> {
+        let tag = input.trim().trim_start_matches('#').to_lowercase();
+        let regex = Regex::new(&format!("^{}$", TAG_PATTERN)).unwrap();
+
+        if !regex.is_match(&tag) {
+            return Err(TodoError::new_from_msg(format!(
+                "Invalid tag '{}'. Tags must be 1-30 letters, numbers, - or _.",
+                input.trim()
+            )));
+        }
+
+        Ok(tag)
+    }
+
+    // Parse a space separated list of tags. "none" gives an empty list.
+    pub fn parse_tags(input: &str) -> Result<BTreeSet<String>, TodoError> {
+        if input.trim().eq_ignore_ascii_case("none") {
+            return Ok(BTreeSet::new());
+        }
+
+        input.split_whitespace().map(TodoItem::parse_tag).collect()
+    }
+
+    // Parse a due date in any of the forms accepted when creating an item
+    pub fn parse_due_date(input: &str) -> Result<NaiveDate, TodoError> {
+        due_date::parse(input, due_date::today())
+    }
+
+    // Parse a due date optionally followed by a due time, such as "fri 3pm". "none" gives neither.
+    pub fn parse_due(input: &str) -> Result<(Option<NaiveDate>, Option<DueTime>), TodoError> {
+        if input.trim().eq_ignore_ascii_case("none") {
+            return Ok((None, None));
+        }
+
+        let (due_date, due_time) = due_date::parse_with_time(input, due_date::today())?;
+        Ok((Some(due_date), due_time))
+    }
+
+    // Any Unicode text is allowed, as long as it is not blank and stays on one line
+    pub fn validate_title(title: &str) -> Result<(), TodoError> {
+        if title.trim().is_empty() || title.chars().any(char::is_control) {
+            return Err(TodoError::new_from_msg(String::from(
+                "Title must not be blank or contain line breaks, tabs or other control characters.",
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Counted in characters rather than bytes so accented letters, CJK and emoji count as one each
+    pub fn validate_title_length(title: &str, max_length: usize) -> Result<(), TodoError> {
+        if title.chars().count() > max_length {
+            return Err(TodoError::new_from_msg(format!(
+                "Title must be at most {} characters long.",
+                max_length
+            )));
+        }
+
+        Ok(())
+    }
+
     pub fn deserialize(dto: TodoItemSerializable) -> Result<Self, TodoError> {
-        let due_date = NaiveDate::parse_from_str(&dto.due_date, "%Y-%m-%d").unwrap();
+        let due_date = dto
+            .due_date
+            .map(|due_date| NaiveDate::parse_from_str(&due_date, "%Y-%m-%d"))
+            .transpose()
+            .map_err(|err| {
+                TodoError::new(
+                    format!("Item {} has an invalid due date.", dto.id),
+                    Box::new(err),
+                )
+            })?;
 
         Ok(TodoItem {
             id: dto.id,
             title: dto.title,
             due_date,
+            due_time: dto.due_time,
             complete: dto.complete,
+            priority: dto.priority,
+            tags: dto.tags,
+            project: dto.project,
+            parent: dto.parent,
+            blocked_by: dto.blocked_by,
+            recurrence: dto.recurrence,
+            recurs_from: dto.recurs_from,
+            created_at: dto.created_at,
+            completed_at: dto.completed_at,
+            updated_at: dto.updated_at,
         })
     }
 
-    pub fn mark_as_done(&mut self) {
+    // at is None when replaying changes recorded before timestamps were
+    pub fn mark_as_done(&mut self, at: Option<DateTime<Utc>>) {
         self.complete = true;
+        self.completed_at = at;
+        self.updated_at = at.or(self.updated_at);
+    }
+
+    pub fn reopen(&mut self, at: Option<DateTime<Utc>>) {
+        self.complete = false;
+        self.completed_at = None;
+        self.updated_at = at.or(self.updated_at);
+    }
+
+    // Apply the given changes, validating them the same way as TodoItem::new
+    pub fn update(&mut self, changes: ItemChanges) -> Result<(), TodoError> {
+        if let Some(title) = &changes.title {
+            TodoItem::validate_title(title)?;
+        }
+
+        if let Some(title) = changes.title {
+            self.title = title;
+        }
+        if let Some(due_date) = changes.due_date {
+            self.due_date = due_date;
+        }
+        if let Some(due_time) = changes.due_time {
+            self.due_time = due_time;
+        }
+        if self.due_date.is_none() {
+            self.due_time = None;
+        }
+        if let Some(priority) = changes.priority {
+            self.priority = priority;
+        }
+        if let Some(tags) = changes.tags {
+            self.tags = tags;
+        }
+        if let Some(project) = changes.project {
+            self.project = project;
+        }
+        if let Some(parent) = changes.parent {
+            self.parent = parent;
+        }
+        if let Some(blocked_by) = changes.blocked_by {
+            self.blocked_by = blocked_by;
+        }
+        if let Some(recurrence) = changes.recurrence {
+            self.recurrence = match (recurrence, self.due_date) {
+                (Some(recurrence), Some(due_date)) => Some(recurrence.pinned_to(due_date)),
+                (recurrence, _) => recurrence,
+            };
+        }
+        self.updated_at = Some(Utc::now());
+        Ok(())
+    }
+
+    // Tags are matched case-insensitively and with or without the leading '#'
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags
+            .contains(&tag.trim().trim_start_matches('#').to_lowercase())
+    }
+
+    pub fn tags_display(&self) -> String {
+        self.tags
+            .iter()
+            .map(|tag| format!("#{}", tag))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    // The moment the item is due, if it has a due date. Items without a due time are due by the
+    // end of their due date and times without a zone are in the local time zone.
+    pub fn deadline(&self) -> Option<DateTime<Utc>> {
+        let time = self
+            .due_time
+            .map_or(NaiveTime::from_hms_opt(23, 59, 59).unwrap(), |due_time| due_time.time);
+        let deadline = self.due_date?.and_time(time);
+
+        Some(match self.due_time.and_then(|due_time| due_time.zone) {
+            Some(zone) => match zone.from_local_datetime(&deadline).single() {
+                Some(deadline) => deadline.with_timezone(&Utc),
+                // At the edge of the date range the offset can push the deadline past it
+                None if zone.local_minus_utc() < 0 => DateTime::<Utc>::MAX_UTC,
+                None => DateTime::<Utc>::MIN_UTC,
+            },
+            // A local time skipped by a daylight saving change is treated as UTC
+            None => Local
+                .from_local_datetime(&deadline)
+                .earliest()
+                .map_or(Utc.from_utc_datetime(&deadline), |deadline| {
+                    deadline.with_timezone(&Utc)
+                }),
+        })
+    }
+
+    pub fn is_overdue(&self, now: DateTime<Utc>) -> bool {
+        !self.complete && self.deadline().is_some_and(|deadline| deadline < now)
+    }
+
+    // Empty for items without a due date
+    pub fn due_display(&self) -> String {
+        match (self.due_date, self.due_time) {
+            (Some(due_date), Some(due_time)) => format!("{} {}", due_date, due_time),
+            (Some(due_date), None) => due_date.to_string(),
+            (None, _) => String::new(),
+        }
+    }
+
+    // The item that replaces a recurring item completed on completed_on. It keeps everything but
+    // the blockers, which belong to this occurrence. Undated items repeat from their completion.
+    pub fn next_occurrence(&self, id: usize, completed_on: NaiveDate) -> Option<TodoItem> {
+        let due_date = self.due_date.unwrap_or(completed_on);
+        let recurrence = self.recurrence.as_ref()?.pinned_to(due_date);
+        let now = Utc::now();
+
+        Some(TodoItem {
+            id,
+            due_date: Some(recurrence.next_due_date(due_date, completed_on)?),
+            recurrence: Some(recurrence),
+            recurs_from: Some(self.id),
+            complete: false,
+            blocked_by: BTreeSet::new(),
+            created_at: Some(now),
+            completed_at: None,
+            updated_at: Some(now),
+            ..self.clone()
+        })
+    }
+
+    pub fn timestamp(&self, timestamp: Timestamp) -> Option<DateTime<Utc>> {
+        match timestamp {
+            Timestamp::Created => self.created_at,
+            Timestamp::Completed => self.completed_at,
+            Timestamp::Updated => self.updated_at,
+        }
+    }
+
+    // Shown in local time, or empty when the moment was never recorded
+    pub fn timestamp_display(&self, timestamp: Timestamp) -> String {
+        self.timestamp(timestamp).map_or(String::new(), |at| {
+            at.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+        })
+    }
+
+    // Sort position of the priority: high first, items without a priority last
+    pub fn priority_rank(&self) -> u8 {
+        self.priority.map_or(3, |priority| priority as u8)
     }
 }
 
@@ -66,8 +643,19 @@ impl From<&TodoItem> for TodoItemSerializable {
         TodoItemSerializable {
             id: item.id,
             title: item.title.clone(),
-            due_date: item.due_date.to_string(),
+            due_date: item.due_date.map(|due_date| due_date.to_string()),
+            due_time: item.due_time,
             complete: item.complete,
+            priority: item.priority,
+            tags: item.tags.clone(),
+            project: item.project.clone(),
+            parent: item.parent,
+            blocked_by: item.blocked_by.clone(),
+            recurrence: item.recurrence.clone(),
+            recurs_from: item.recurs_from,
+            created_at: item.created_at,
+            completed_at: item.completed_at,
+            updated_at: item.updated_at,
         }
     }
 }
@@ -82,7 +670,10 @@ impl fmt::Display for TodoItem {
         write!(
             f,
             " {} | {} | {} | {}",
-            self.id, is_done_indicator, self.due_date, self.title
+            self.id,
+            is_done_indicator,
+            self.due_display(),
+            self.title
         )
     }
 }
@@ -97,7 +688,7 @@ mod todoitem_new_tests {
         let result = TodoItem::new(String::from(user_input), 0).unwrap();
 
         let expected_date = NaiveDate::parse_from_str("2021-01-01", "%Y-%m-%d").unwrap();
-        assert_eq!(expected_date, result.due_date);
+        assert_eq!(Some(expected_date), result.due_date);
         assert_eq!("First Todo", result.title);
         assert!(!result.complete);
     }
@@ -108,25 +699,376 @@ mod todoitem_new_tests {
         let result = TodoItem::new(String::from(user_input), 0).unwrap();
 
         let expected_date = NaiveDate::parse_from_str("2022-01-01", "%Y-%m-%d").unwrap();
-        assert_eq!(expected_date, result.due_date);
+        assert_eq!(Some(expected_date), result.due_date);
         assert_eq!("First Todo", result.title);
         assert!(!result.complete);
     }
 
     #[test]
     fn bad_input() {
-        let user_input = "BAD INPUT";
+        let user_input = "2023-1-1 First Todo";
         let error = TodoItem::new(String::from(user_input), 0).err().unwrap();
 
-        assert_eq!("Invalid format for new Todo item.", &error.message);
+        assert_eq!(
+            "Unrecognized due date '2023-1-1'. Expected YYYY-MM-DD, today, tomorrow, a weekday like fri, next monday, +3d, +2w, in 2 weeks or eom.",
+            &error.message
+        );
+    }
+
+    #[test]
+    fn without_due_date() {
+        let result = TodoItem::new(String::from("Learn Rust #someday"), 0).unwrap();
+
+        assert_eq!(None, result.due_date);
+        assert_eq!("Learn Rust", result.title);
+        assert_eq!("", result.due_display());
+        assert_eq!(None, result.deadline());
+        assert!(!result.is_overdue(Utc::now()));
     }
 
     #[test]
     fn bad_input_too_long() {
         let user_input =
             String::from("2022-01-01 Todo Item") + String::from("0").repeat(50).as_str();
-        let error = TodoItem::new(String::from(user_input), 0).err().unwrap();
+        let error = TodoItem::new(user_input, 0).err().unwrap();
+
+        assert_eq!("Title must be at most 50 characters long.", &error.message);
+    }
 
+    #[test]
+    fn unicode_titles() {
+        for title in ["Café, crème brûlée", "Call Mom's dentist", "お茶を買う", "Ship it 🚀"] {
+            let item = TodoItem::new(format!("2023-01-01 {}", title), 0).unwrap();
+            assert_eq!(title, item.title);
+        }
+
+        // Length is counted in characters, not bytes
+        let title = "日".repeat(DEFAULT_MAX_TITLE_LENGTH);
+        assert!(TodoItem::new(format!("2023-01-01 {}", title), 0).is_ok());
+        assert!(TodoItem::validate_title_length(&title, DEFAULT_MAX_TITLE_LENGTH - 1).is_err());
+    }
+
+    #[test]
+    fn relative_due_dates() {
+        // A Wednesday
+        let today = NaiveDate::from_ymd_opt(2023, 1, 18).unwrap();
+
+        let input = TodoItem::parse_input_relative_to("in 2 weeks Sprint review #ops", today).unwrap();
+        assert_eq!(Some(NaiveDate::from_ymd_opt(2023, 2, 1).unwrap()), input.due_date);
+        assert_eq!("Sprint review", input.title);
+
+        let input = TodoItem::parse_input_relative_to("next monday Monday standup", today).unwrap();
+        assert_eq!(Some(NaiveDate::from_ymd_opt(2023, 1, 23).unwrap()), input.due_date);
+        assert_eq!("Monday standup", input.title);
+
+        let input = TodoItem::parse_input_relative_to("on fri Deploy", today).unwrap();
+        assert_eq!(Some(NaiveDate::from_ymd_opt(2023, 1, 20).unwrap()), input.due_date);
+
+        let input = TodoItem::parse_input_relative_to("Sun cream", today).unwrap();
+        assert_eq!(None, input.due_date);
+        assert_eq!("Sun cream", input.title);
+        let input = TodoItem::parse_input_relative_to("Friday drinks", today).unwrap();
+        assert_eq!(None, input.due_date);
+        assert_eq!("Friday drinks", input.title);
+
+        let error = TodoItem::parse_input_relative_to("tomorrow", today).err().unwrap();
         assert_eq!("Invalid format for new Todo item.", &error.message);
     }
+
+    #[test]
+    fn due_time_and_zone() {
+        let item = TodoItem::new(String::from("2023-01-01 3pm +02:00 Call Berlin"), 0).unwrap();
+
+        assert_eq!("Call Berlin", item.title);
+        assert_eq!("2023-01-01 15:00 +02:00", item.due_display());
+        assert_eq!("2023-01-01T13:00:00+00:00", item.deadline().unwrap().to_rfc3339());
+        assert!(item.is_overdue(Utc.with_ymd_and_hms(2023, 1, 1, 13, 0, 1).unwrap()));
+        assert!(!item.is_overdue(Utc.with_ymd_and_hms(2023, 1, 1, 12, 59, 0).unwrap()));
+
+        // Without a time the item is due by the end of the day
+        let item = TodoItem::new(String::from("2023-01-01 Call Berlin"), 0).unwrap();
+        assert_eq!(None, item.due_time);
+        assert_eq!("2023-01-01", item.due_display());
+    }
+
+    #[test]
+    fn deadline_at_the_edge_of_the_date_range() {
+        let mut item = TodoItem::from_parts(0, Some(NaiveDate::MAX), String::from("Edge")).unwrap();
+        item.due_time = Some(DueTime::parse("23:00 -12:00").unwrap());
+        assert_eq!(Some(DateTime::<Utc>::MAX_UTC), item.deadline());
+        assert!(!item.is_overdue(Utc::now()));
+
+        let mut item = TodoItem::from_parts(0, Some(NaiveDate::MIN), String::from("Edge")).unwrap();
+        item.due_time = Some(DueTime::parse("01:00 +14:00").unwrap());
+        assert_eq!(Some(DateTime::<Utc>::MIN_UTC), item.deadline());
+        assert!(item.is_overdue(Utc::now()));
+    }
+
+    #[test]
+    fn due_time_serialization() {
+        let item = TodoItem::new(String::from("2023-01-01 9:30am UTC Standup"), 0).unwrap();
+        let json = serde_json::to_string(&TodoItemSerializable::from(&item)).unwrap();
+        assert!(json.contains(r#""due_time":"09:30 UTC""#));
+
+        let dto: TodoItemSerializable = serde_json::from_str(&json).unwrap();
+        assert_eq!(item.due_time, TodoItem::deserialize(dto).unwrap().due_time);
+
+        // Items saved before due times existed have none
+        let dto: TodoItemSerializable = serde_json::from_str(
+            r#"{"id": 0, "title": "Standup", "due_date": "2023-01-01", "complete": false}"#,
+        )
+        .unwrap();
+        assert_eq!(None, dto.due_time);
+    }
+
+    #[test]
+    fn timestamps() {
+        let before = Utc::now();
+        let mut item = TodoItem::new(String::from("2023-01-01 Standup"), 0).unwrap();
+        let created_at = item.created_at.unwrap();
+        assert!(created_at >= before);
+        assert_eq!(Some(created_at), item.updated_at);
+        assert_eq!(None, item.completed_at);
+
+        let completed_at = Utc.with_ymd_and_hms(2023, 1, 2, 9, 30, 0).unwrap();
+        item.mark_as_done(Some(completed_at));
+        assert_eq!(Some(completed_at), item.completed_at);
+        assert_eq!(Some(completed_at), item.updated_at);
+
+        item.reopen(None);
+        assert_eq!(None, item.completed_at);
+        assert_eq!(Some(completed_at), item.updated_at);
+
+        // Items saved before timestamps existed have none
+        let dto: TodoItemSerializable = serde_json::from_str(
+            r#"{"id": 0, "title": "Standup", "due_date": "2023-01-01", "complete": true}"#,
+        )
+        .unwrap();
+        let item = TodoItem::deserialize(dto).unwrap();
+        assert_eq!(None, item.created_at);
+        assert_eq!("", item.timestamp_display(Timestamp::Completed));
+    }
+
+    #[test]
+    fn timestamp_lists() {
+        assert_eq!(
+            vec![Timestamp::Created, Timestamp::Updated],
+            Timestamp::parse_list("created, updated,created").unwrap()
+        );
+        assert!(Timestamp::parse_list("none").unwrap().is_empty());
+        assert_eq!(
+            "Unknown timestamp 'deleted'. Expected created, completed or updated.",
+            &Timestamp::parse_list("created,deleted").err().unwrap().message
+        );
+    }
+
+    #[test]
+    fn priority_marker() {
+        let result = TodoItem::new(String::from("2023-01-01 !high Deploy fix"), 0).unwrap();
+
+        assert_eq!(Some(Priority::High), result.priority);
+        assert_eq!("Deploy fix", result.title);
+
+        let result = TodoItem::new(String::from("2023-01-01 Deploy fix !l"), 0).unwrap();
+        assert_eq!(Some(Priority::Low), result.priority);
+    }
+
+    #[test]
+    fn tag_markers() {
+        let result = TodoItem::new(String::from("2023-01-01 Rotate keys #ops #Backend"), 0).unwrap();
+
+        assert_eq!("Rotate keys", result.title);
+        assert_eq!(
+            vec!["backend", "ops"],
+            result.tags.iter().map(String::as_str).collect::<Vec<&str>>()
+        );
+        assert!(result.has_tag("#OPS"));
+        assert!(!result.has_tag("frontend"));
+        assert_eq!("#backend #ops", result.tags_display());
+    }
+
+    #[test]
+    fn project_marker() {
+        let result = TodoItem::new(String::from("2023-01-01 @Work Quarterly report"), 0).unwrap();
+
+        assert_eq!(Some(String::from("Work")), result.project);
+        assert_eq!("Quarterly report", result.title);
+    }
+
+    #[test]
+    fn parent_marker() {
+        let result = TodoItem::new(String::from("2023-01-01 Write tests ^3"), 4).unwrap();
+
+        assert_eq!(Some(3), result.parent);
+        assert_eq!("Write tests", result.title);
+        let result = TodoItem::new(String::from("2023-01-01 Write tests ^x"), 4).unwrap();
+        assert_eq!(None, result.parent);
+        assert_eq!("Write tests ^x", result.title);
+    }
+
+    #[test]
+    fn recurrence_marker() {
+        let input = TodoItem::parse_input("2023-01-01 Pay invoices *monthly:1").unwrap();
+
+        assert_eq!("Pay invoices", input.title);
+        assert_eq!(Some(Recurrence::Monthly { day: Some(1) }), input.recurrence);
+        let input = TodoItem::parse_input("2023-01-01 Pay invoices *yearly").unwrap();
+        assert_eq!(None, input.recurrence);
+        assert_eq!("Pay invoices *yearly", input.title);
+    }
+
+    #[test]
+    fn blocker_list() {
+        let blockers = TodoItem::parse_blockers("3, 4 7").unwrap();
+
+        assert_eq!(vec![3, 4, 7], blockers.into_iter().collect::<Vec<usize>>());
+        assert!(TodoItem::parse_blockers("none").unwrap().is_empty());
+        assert!(TodoItem::parse_blockers("3 four").is_err());
+    }
+
+    #[test]
+    fn bad_tag_marker() {
+        let result = TodoItem::new(String::from("2023-01-01 Todo #"), 0).unwrap();
+
+        assert_eq!("Todo #", result.title);
+        assert!(result.tags.is_empty());
+    }
+
+    #[test]
+    fn bad_priority_marker() {
+        let result = TodoItem::new(String::from("2023-01-01 Todo !urgent"), 0).unwrap();
+
+        assert_eq!("Todo !urgent", result.title);
+        assert_eq!(None, result.priority);
+    }
+
+    #[test]
+    fn marker_lookalikes_stay_in_title() {
+        for title in ["Fix *urgent* bug", "Wow !!", "Rank #1 item", "Smile ^_^", "Email @b.o.b"] {
+            let input = TodoItem::parse_input(title).unwrap();
+            assert_eq!(title, input.title);
+            assert!(input.tags.is_empty() && input.priority.is_none() && input.project.is_none());
+            assert!(input.parent.is_none() && input.recurrence.is_none());
+        }
+    }
+
+    #[test]
+    fn escaped_markers() {
+        let input = TodoItem::parse_input(r"Tweet \#rustlang \!high \@home #social").unwrap();
+        assert_eq!("Tweet #rustlang !high @home", input.title);
+        assert_eq!(vec!["social"], input.tags.iter().map(String::as_str).collect::<Vec<&str>>());
+
+        // An escaped first word is title text, not a due date
+        let input = TodoItem::parse_input(r"\Tomorrow never dies").unwrap();
+        assert_eq!(None, input.due_date);
+        assert_eq!("Tomorrow never dies", input.title);
+        // A lone backslash is kept
+        assert_eq!(r"Back \ slash", TodoItem::parse_input(r"Back \ slash").unwrap().title);
+    }
+
+    #[test]
+    fn unknown_projects_stay_in_title() {
+        let today = NaiveDate::from_ymd_opt(2023, 1, 18).unwrap();
+        let input =
+            TodoItem::parse_input_with_projects("Email @bob @work", today, |name| name == "work")
+                .unwrap();
+
+        assert_eq!("Email @bob", input.title);
+        assert_eq!(Some(String::from("work")), input.project);
+    }
+
+    #[test]
+    fn bad_input_invalid_date() {
+        let user_input = "2022-02-31 First Todo";
+        let error = TodoItem::new(String::from(user_input), 0).err().unwrap();
+
+        assert_eq!(
+            "Due date must be a valid date in the format YYYY-MM-DD.",
+            &error.message
+        );
+    }
+}
+
+#[cfg(test)]
+mod todoitem_from_parts_tests {
+    use super::*;
+
+    #[test]
+    fn greenpath() {
+        let due_date = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+        let result = TodoItem::from_parts(4, Some(due_date), String::from("Todo")).unwrap();
+
+        assert_eq!(4, result.id);
+        assert_eq!(Some(due_date), result.due_date);
+        assert_eq!("Todo", result.title);
+        assert!(!result.complete);
+    }
+
+    #[test]
+    fn bad_title() {
+        let due_date = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+        let error = TodoItem::from_parts(0, Some(due_date), String::from("")).err().unwrap();
+
+        assert_eq!(
+            "Title must not be blank or contain line breaks, tabs or other control characters.",
+            &error.message
+        );
+    }
+}
+
+#[cfg(test)]
+mod todoitem_update_tests {
+    use super::*;
+
+    #[test]
+    fn greenpath() {
+        let mut item = TodoItem::new(String::from("2023-01-01 Typo Tood"), 0).unwrap();
+        let due_date = NaiveDate::from_ymd_opt(2023, 2, 2).unwrap();
+
+        item.update(ItemChanges {
+            title: Some(String::from("Typo Todo")),
+            due_date: Some(Some(due_date)),
+            ..ItemChanges::default()
+        })
+        .unwrap();
+
+        assert_eq!("Typo Todo", item.title);
+        assert_eq!(Some(due_date), item.due_date);
+        assert!(item.updated_at >= item.created_at);
+    }
+
+    #[test]
+    fn priority_set_and_cleared() {
+        let mut item = TodoItem::new(String::from("2023-01-01 Todo !high"), 0).unwrap();
+
+        item.update(ItemChanges {
+            priority: Some(None),
+            ..ItemChanges::default()
+        })
+        .unwrap();
+
+        assert_eq!(None, item.priority);
+        assert_eq!("Todo", item.title);
+    }
+
+    #[test]
+    fn bad_title_leaves_item_unchanged() {
+        let mut item = TodoItem::new(String::from("2023-01-01 Todo"), 0).unwrap();
+
+        let error = item
+            .update(ItemChanges {
+                title: Some(String::from("Bad\ntitle")),
+                due_date: Some(Some(NaiveDate::from_ymd_opt(2023, 2, 2).unwrap())),
+                ..ItemChanges::default()
+            })
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            "Title must not be blank or contain line breaks, tabs or other control characters.",
+            &error.message
+        );
+        assert_eq!("Todo", item.title);
+        assert_eq!(Some(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap()), item.due_date);
+    }
 }
@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+
+use crate::err::TodoError;
+use crate::todo::action::StoreAction;
+
+const JOURNAL_FILE_EXTENSION: &str = "journal";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalEntry {
+    seq: u64,
+    #[serde(flatten)]
+    action: StoreAction,
+}
+
+// Append-only log of StoreActions kept next to the snapshot file. Each line is one JSON encoded
+// JournalEntry. Entries carry an increasing sequence number and the snapshot records the last one
+// it contains, so replaying a journal that was already compacted is a no-op.
+pub struct Journal {
+    filepath: String,
+    last_seq: u64,
+    entry_count: usize,
+}
+
+pub struct JournalReplay {
+    pub actions: Vec<StoreAction>,
+    // A torn final line means the process died mid-append. That entry never completed and is dropped.
+    pub torn_tail: bool,
+}
+
+impl Journal {
+    pub fn new(persistence_filepath: &str, last_seq: u64) -> Journal {
+        Journal {
+            filepath: format!("{}.{}", persistence_filepath, JOURNAL_FILE_EXTENSION),
+            last_seq,
+            entry_count: 0,
+        }
+    }
+
+    // Read all entries newer than the snapshot. Only the final line may be unreadable; anything
+    // else means the journal was modified by something other than this program.
+    pub fn replay(&mut self) -> Result<JournalReplay, TodoError> {
+        let file = match File::open(&self.filepath) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                return Ok(JournalReplay {
+                    actions: Vec::new(),
+                    torn_tail: false,
+                })
+            }
+            Err(err) => {
+                return Err(TodoError::new(
+                    String::from("Error opening journal file."),
+                    Box::new(err),
+                ))
+            }
+        };
+
+        let lines = BufReader::new(file)
+            .lines()
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|err| {
+                TodoError::new(String::from("Error reading journal file."), Box::new(err))
+            })?;
+
+        let mut actions = Vec::new();
+        let mut torn_tail = false;
+        for (i, line) in lines.iter().enumerate() {
+            let entry = match serde_json::from_str::<JournalEntry>(line) {
+                Ok(entry) => entry,
+                Err(_) if i == lines.len() - 1 => {
+                    torn_tail = true;
+                    break;
+                }
+                Err(err) => {
+                    return Err(TodoError::new(
+                        format!("Journal entry {} is corrupted.", i + 1),
+                        Box::new(err),
+                    ))
+                }
+            };
+
+            self.entry_count += 1;
+            if entry.seq > self.last_seq {
+                self.last_seq = entry.seq;
+                actions.push(entry.action);
+            }
+        }
+
+        Ok(JournalReplay { actions, torn_tail })
+    }
+
+    pub fn append(&mut self, action: &StoreAction) -> Result<(), TodoError> {
+        let entry = JournalEntry {
+            seq: self.last_seq + 1,
+            action: action.clone(),
+        };
+        let mut line = serde_json::to_string(&entry).map_err(|err| {
+            TodoError::new(String::from("Error serializing journal entry."), Box::new(err))
+        })?;
+        line.push('\n');
+
+        let mut journal = File::options()
+            .create(true)
+            .append(true)
+            .open(&self.filepath)
+            .map_err(|err| {
+                TodoError::new(String::from("Error opening journal file."), Box::new(err))
+            })?;
+        journal
+            .write_all(line.as_bytes())
+            .and_then(|_| journal.sync_data())
+            .map_err(|err| {
+                TodoError::new(String::from("Error writing journal entry."), Box::new(err))
+            })?;
+
+        self.last_seq = entry.seq;
+        self.entry_count += 1;
+
+        Ok(())
+    }
+
+    // Only call once the snapshot containing every entry has been written
+    pub fn clear(&mut self) -> Result<(), TodoError> {
+        match fs::remove_file(&self.filepath) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(TodoError::new(
+                    String::from("Error removing journal file."),
+                    Box::new(err),
+                ))
+            }
+        }
+        self.entry_count = 0;
+
+        Ok(())
+    }
+
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.entry_count
+    }
+}
@@ -0,0 +1,40 @@
+use std::fs::{File, TryLockError};
+
+use crate::err::TodoError;
+
+const LOCK_FILE_EXTENSION: &str = "lock";
+
+// Advisory lock held for as long as a store is open. The OS releases it when the process exits,
+// even after a crash, so a stale lock file on disk never blocks a later run.
+pub struct StoreLock {
+    _file: File,
+}
+
+impl StoreLock {
+    pub fn acquire(persistence_filepath: &str) -> Result<StoreLock, TodoError> {
+        let lock_filepath = format!("{}.{}", persistence_filepath, LOCK_FILE_EXTENSION);
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_filepath)
+            .map_err(|err| {
+                TodoError::new(
+                    format!("Error opening lock file {}.", lock_filepath),
+                    Box::new(err),
+                )
+            })?;
+
+        match file.try_lock() {
+            Ok(()) => Ok(StoreLock { _file: file }),
+            Err(TryLockError::WouldBlock) => Err(TodoError::new_from_msg(format!(
+                "{} is in use by another todo_assistant process.",
+                persistence_filepath
+            ))),
+            Err(TryLockError::Error(err)) => Err(TodoError::new(
+                format!("Error locking {}.", lock_filepath),
+                Box::new(err),
+            )),
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use serde_json::{json, Map, Value};
+
+use crate::err::TodoError;
+
+// Version 0: items without ids and no next_id (the original sample data layout)
+// Version 1: items carry ids and the store tracks next_id
+// Version 2: the store records its schema_version
+// Version 3: deleted items are kept in a separate trash list
+// Version 4: the undo and redo stacks are persisted with the store
+// Version 5: the store keeps a list of projects
+// Version 6: the store keeps a list of saved views
+pub const CURRENT_SCHEMA_VERSION: u64 = 6;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), TodoError>;
+
+// MIGRATIONS[n] upgrades a store document from version n to version n + 1
+const MIGRATIONS: [Migration; CURRENT_SCHEMA_VERSION as usize] = [
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+    migrate_v4_to_v5,
+    migrate_v5_to_v6,
+];
+
+// Upgrade a raw store document in place to CURRENT_SCHEMA_VERSION. Returns the version the
+// document was originally at if any migration was applied.
+pub fn migrate(document: &mut Value) -> Result<Option<u64>, TodoError> {
+    let document = document.as_object_mut().ok_or(TodoError::new_from_msg(String::from(
+        "Error reading persistence file. Expected a JSON object.",
+    )))?;
+
+    let original_version = detect_version(document)?;
+    if original_version > CURRENT_SCHEMA_VERSION {
+        return Err(TodoError::new_from_msg(format!(
+            "Persistence file has schema version {} but this version of todo_assistant only supports up to {}.",
+            original_version, CURRENT_SCHEMA_VERSION
+        )));
+    }
+    if original_version == CURRENT_SCHEMA_VERSION {
+        return Ok(None);
+    }
+
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(original_version as usize) {
+        migration(document)?;
+        document.insert(String::from("schema_version"), Value::from(version + 1));
+    }
+
+    Ok(Some(original_version))
+}
+
+fn detect_version(document: &Map<String, Value>) -> Result<u64, TodoError> {
+    if let Some(version) = document.get("schema_version") {
+        return version.as_u64().ok_or(TodoError::new_from_msg(String::from(
+            "Error reading persistence file. schema_version must be a number.",
+        )));
+    }
+
+    // Files written before schema_version existed are told apart by their shape
+    let all_items_have_ids = items(document)?.iter().all(|item| item.get("id").is_some());
+    if document.contains_key("next_id") && all_items_have_ids {
+        Ok(1)
+    } else {
+        Ok(0)
+    }
+}
+
+fn items(document: &Map<String, Value>) -> Result<&Vec<Value>, TodoError> {
+    match document.get("store") {
+        Some(Value::Array(items)) => Ok(items),
+        _ => Err(TodoError::new_from_msg(String::from(
+            "Error reading persistence file. Expected a store array.",
+        ))),
+    }
+}
+
+// Assign ids to items in file order, skipping any ids that are already taken, and derive next_id
+fn migrate_v0_to_v1(document: &mut Map<String, Value>) -> Result<(), TodoError> {
+    let mut next_id = items(document)?
+        .iter()
+        .filter_map(|item| item.get("id").and_then(Value::as_u64))
+        .map(|id| id + 1)
+        .max()
+        .unwrap_or(0);
+
+    if let Some(Value::Array(items)) = document.get_mut("store") {
+        for item in items.iter_mut().filter_map(Value::as_object_mut) {
+            if !item.contains_key("id") {
+                item.insert(String::from("id"), Value::from(next_id));
+                next_id += 1;
+            }
+        }
+    }
+
+    let stored_next_id = document.get("next_id").and_then(Value::as_u64).unwrap_or(0);
+    document.insert(String::from("next_id"), Value::from(next_id.max(stored_next_id)));
+
+    Ok(())
+}
+
+// Only adds schema_version, which migrate records after every step
+fn migrate_v1_to_v2(_document: &mut Map<String, Value>) -> Result<(), TodoError> {
+    Ok(())
+}
+
+fn migrate_v2_to_v3(document: &mut Map<String, Value>) -> Result<(), TodoError> {
+    document
+        .entry("trash")
+        .or_insert_with(|| Value::Array(Vec::new()));
+
+    Ok(())
+}
+
+fn migrate_v3_to_v4(document: &mut Map<String, Value>) -> Result<(), TodoError> {
+    document
+        .entry("history")
+        .or_insert_with(|| json!({"undo": [], "redo": []}));
+
+    Ok(())
+}
+
+fn migrate_v4_to_v5(document: &mut Map<String, Value>) -> Result<(), TodoError> {
+    document
+        .entry("projects")
+        .or_insert_with(|| Value::Array(Vec::new()));
+
+    Ok(())
+}
+
+fn migrate_v5_to_v6(document: &mut Map<String, Value>) -> Result<(), TodoError> {
+    document
+        .entry("views")
+        .or_insert_with(|| Value::Array(Vec::new()));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod migrate_tests {
+    use super::*;
+
+    #[test]
+    fn v0_assigns_ids_and_next_id() {
+        let mut document = json!({
+            "store": [
+                {"title": "One", "due_date": "2023-01-01", "complete": true},
+                {"title": "Two", "due_date": "2023-02-02", "complete": false}
+            ]
+        });
+
+        let original_version = migrate(&mut document).unwrap();
+
+        assert_eq!(Some(0), original_version);
+        assert_eq!(0, document["store"][0]["id"]);
+        assert_eq!(1, document["store"][1]["id"]);
+        assert_eq!(2, document["next_id"]);
+        assert_eq!(CURRENT_SCHEMA_VERSION, document["schema_version"]);
+    }
+
+    #[test]
+    fn v0_keeps_existing_ids() {
+        let mut document = json!({
+            "store": [
+                {"title": "One", "due_date": "2023-01-01", "complete": true},
+                {"id": 5, "title": "Two", "due_date": "2023-02-02", "complete": false}
+            ]
+        });
+
+        migrate(&mut document).unwrap();
+
+        assert_eq!(6, document["store"][0]["id"]);
+        assert_eq!(5, document["store"][1]["id"]);
+        assert_eq!(7, document["next_id"]);
+    }
+
+    #[test]
+    fn v1_gets_schema_version() {
+        let mut document = json!({"store": [], "next_id": 3});
+
+        let original_version = migrate(&mut document).unwrap();
+
+        assert_eq!(Some(1), original_version);
+        assert_eq!(3, document["next_id"]);
+        assert_eq!(CURRENT_SCHEMA_VERSION, document["schema_version"]);
+    }
+
+    #[test]
+    fn v2_gets_empty_trash() {
+        let mut document = json!({"store": [], "next_id": 0, "schema_version": 2});
+
+        let original_version = migrate(&mut document).unwrap();
+
+        assert_eq!(Some(2), original_version);
+        assert_eq!(json!([]), document["trash"]);
+        assert_eq!(json!({"undo": [], "redo": []}), document["history"]);
+        assert_eq!(CURRENT_SCHEMA_VERSION, document["schema_version"]);
+    }
+
+    #[test]
+    fn v4_gets_empty_projects() {
+        let mut document = json!({
+            "store": [],
+            "trash": [],
+            "history": {"undo": [], "redo": []},
+            "next_id": 0,
+            "schema_version": 4
+        });
+
+        let original_version = migrate(&mut document).unwrap();
+
+        assert_eq!(Some(4), original_version);
+        assert_eq!(json!([]), document["projects"]);
+    }
+
+    #[test]
+    fn v5_gets_empty_views() {
+        let mut document = json!({
+            "store": [],
+            "trash": [],
+            "history": {"undo": [], "redo": []},
+            "projects": [],
+            "next_id": 0,
+            "schema_version": 5
+        });
+
+        let original_version = migrate(&mut document).unwrap();
+
+        assert_eq!(Some(5), original_version);
+        assert_eq!(json!([]), document["views"]);
+    }
+
+    #[test]
+    fn current_version_untouched() {
+        let mut document = json!({
+            "store": [],
+            "trash": [],
+            "history": {"undo": [], "redo": []},
+            "projects": [],
+            "views": [],
+            "next_id": 0,
+            "schema_version": CURRENT_SCHEMA_VERSION
+        });
+
+        assert_eq!(None, migrate(&mut document).unwrap());
+    }
+
+    #[test]
+    fn newer_version_rejected() {
+        let mut document = json!({"store": [], "next_id": 0, "schema_version": 99});
+
+        assert!(migrate(&mut document).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::err::TodoError;
+
+const PROJECT_NAME_PATTERN: &str = r"[A-Za-z0-9-_]{1,30}";
+
+// A named list of items. Items refer to their project by name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+    pub name: String,
+    // Archived projects keep their items but hide them from the regular lists
+    pub archived: bool,
+}
+
+impl Project {
+    pub fn new(name: &str) -> Result<Project, TodoError> {
+        let name = Project::parse_name(name)?;
+
+        Ok(Project {
+            name,
+            archived: false,
+        })
+    }
+
+    // Project names double as the @project marker in new item input, so they cannot contain spaces
+    pub fn parse_name(input: &str) -> Result<String, TodoError> {
+        let name = input.trim().trim_start_matches('@');
+        let regex = Regex::new(&format!("^{}$", PROJECT_NAME_PATTERN)).unwrap();
+
+        if !regex.is_match(name) {
+            return Err(TodoError::new_from_msg(format!(
+                "Invalid project name '{}'. Project names must be 1-30 letters, numbers, - or _.",
+                input.trim()
+            )));
+        }
+
+        Ok(String::from(name))
+    }
+
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name
+            .eq_ignore_ascii_case(name.trim().trim_start_matches('@'))
+    }
+}
+
+impl fmt::Display for Project {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.archived {
+            true => write!(f, "{} (archived)", self.name),
+            false => write!(f, "{}", self.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod project_name_tests {
+    use super::*;
+
+    #[test]
+    fn greenpath() {
+        assert_eq!("Work", Project::parse_name(" @Work ").unwrap());
+        assert!(Project::new("Work").unwrap().is_named("work"));
+    }
+
+    #[test]
+    fn bad_name() {
+        let error = Project::parse_name("Side project").err().unwrap();
+
+        assert_eq!(
+            "Invalid project name 'Side project'. Project names must be 1-30 letters, numbers, - or _.",
+            &error.message
+        );
+    }
+}
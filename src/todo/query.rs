@@ -0,0 +1,470 @@
+use crate::err::TodoError;
+use crate::todo::due_date;
+use crate::todo::{Priority, Timestamp, TodoItem, TodoStore};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::cmp::Ordering;
+use unicode_width::UnicodeWidthStr;
+
+pub const QUERY_FIELDS: &str = "status, due, tag, project, priority, title or sort";
+
+// A filter and sort expression such as `status:open due<2023-02-01 tag:ops title~"deploy"
+// sort:-due`. Every filter term has to match, a leading '-' negates a term and words without a
+// field are searched for in the title.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    terms: Vec<Term>,
+    sort_keys: Vec<SortKey>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    filter: Filter,
+    negated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Status(Status),
+    Due(Comparison, NaiveDate),
+    Undated,
+    Tag(String),
+    // None matches items outside of every project
+    Project(Option<String>),
+    Priority(Option<Priority>),
+    // Lowercased text searched for anywhere in the title
+    TitleContains(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Open,
+    Done,
+    Ready,
+    Blocked,
+    Overdue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Before,
+    OnOrBefore,
+    On,
+    OnOrAfter,
+    After,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SortKey {
+    field: SortField,
+    descending: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortField {
+    Id,
+    Due,
+    Priority,
+    Title,
+    Timestamp(Timestamp),
+}
+
+// A whitespace separated piece of the query. start and length are in bytes of the original
+// input so errors can point at it.
+struct Token {
+    text: String,
+    start: usize,
+    length: usize,
+}
+
+// Longest operators first so "<=" is not read as "<"
+const OPERATORS: [&str; 7] = ["<=", ">=", "<", ">", "=", ":", "~"];
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query, TodoError> {
+        Query::parse_relative_to(input, due_date::today())
+    }
+
+    // Relative due dates such as "due<+7d" are resolved against today
+    pub fn parse_relative_to(input: &str, today: NaiveDate) -> Result<Query, TodoError> {
+        let mut query = Query {
+            terms: Vec::new(),
+            sort_keys: Vec::new(),
+        };
+
+        for token in tokenize(input)? {
+            query
+                .parse_token(&token.text, today)
+                .map_err(|err| point_at(input, token.start, token.length, &err.message))?;
+        }
+
+        Ok(query)
+    }
+
+    fn parse_token(&mut self, token: &str, today: NaiveDate) -> Result<(), TodoError> {
+        let (negated, term) = match token.strip_prefix('-') {
+            Some(term) if !term.is_empty() => (true, term),
+            _ => (false, token),
+        };
+
+        let (field, operator, value) = match split_term(term) {
+            Some(parts) => parts,
+            None => {
+                self.terms.push(Term {
+                    filter: Filter::TitleContains(term.to_lowercase()),
+                    negated,
+                });
+                return Ok(());
+            }
+        };
+        if value.is_empty() {
+            return Err(TodoError::new_from_msg(format!("'{}' needs a value.", term)));
+        }
+
+        let filter = match (field.to_lowercase().as_str(), operator) {
+            ("sort", ":") if !negated => {
+                for key in value.split(',') {
+                    self.sort_keys.push(SortKey::parse(key)?);
+                }
+                return Ok(());
+            }
+            ("sort", _) if negated => {
+                return Err(TodoError::new_from_msg(String::from(
+                    "Sort terms cannot be negated. Use sort:-FIELD to sort in descending order.",
+                )))
+            }
+            ("status" | "is", ":") => Filter::Status(Status::parse(value)?),
+            ("due", ":" | "=") if value.eq_ignore_ascii_case("none") => Filter::Undated,
+            ("due", operator) if operator != "~" => Filter::Due(
+                Comparison::parse(operator),
+                due_date::parse(value, today)?,
+            ),
+            ("tag", ":") => Filter::Tag(TodoItem::parse_tag(value)?),
+            ("project", ":") => match value.to_lowercase().as_str() {
+                "none" => Filter::Project(None),
+                _ => Filter::Project(Some(String::from(value.trim_start_matches('@')))),
+            },
+            ("priority", ":") => Filter::Priority(Priority::parse_optional(value)?),
+            ("title", ":" | "~") => Filter::TitleContains(value.to_lowercase()),
+            ("sort" | "status" | "is" | "due" | "tag" | "project" | "priority" | "title", _) => {
+                return Err(TodoError::new_from_msg(format!(
+                    "'{}' cannot be used with '{}'.",
+                    operator, field
+                )))
+            }
+            _ => {
+                return Err(TodoError::new_from_msg(format!(
+                    "Unknown field '{}'. Expected {}.",
+                    field, QUERY_FIELDS
+                )))
+            }
+        };
+        self.terms.push(Term { filter, negated });
+
+        Ok(())
+    }
+
+    pub fn matches(&self, item: &TodoItem, store: &TodoStore, now: DateTime<Utc>) -> bool {
+        self.terms
+            .iter()
+            .all(|term| term.filter.matches(item, store, now) != term.negated)
+    }
+
+    // Stable, so items that compare equal on every sort key keep their current order
+    pub fn sort(&self, items: &mut [&TodoItem]) {
+        if self.sort_keys.is_empty() {
+            return;
+        }
+
+        items.sort_by(|a, b| {
+            self.sort_keys
+                .iter()
+                .map(|key| key.compare(a, b))
+                .find(|ordering| *ordering != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+}
+
+impl Filter {
+    fn matches(&self, item: &TodoItem, store: &TodoStore, now: DateTime<Utc>) -> bool {
+        match self {
+            Filter::Status(Status::Open) => !item.complete,
+            Filter::Status(Status::Done) => item.complete,
+            Filter::Status(Status::Ready) => !item.complete && !store.is_blocked(item),
+            Filter::Status(Status::Blocked) => !item.complete && store.is_blocked(item),
+            Filter::Status(Status::Overdue) => item.is_overdue(now),
+            Filter::Due(comparison, date) => item
+                .due_date
+                .is_some_and(|due_date| comparison.holds(due_date.cmp(date))),
+            Filter::Undated => item.due_date.is_none(),
+            Filter::Tag(tag) => item.has_tag(tag),
+            Filter::Project(None) => item.project.is_none(),
+            Filter::Project(Some(name)) => item
+                .project
+                .as_ref()
+                .is_some_and(|project| project.eq_ignore_ascii_case(name)),
+            Filter::Priority(priority) => item.priority == *priority,
+            Filter::TitleContains(text) => item.title.to_lowercase().contains(text),
+        }
+    }
+}
+
+impl Status {
+    fn parse(input: &str) -> Result<Status, TodoError> {
+        match input.to_lowercase().as_str() {
+            "open" | "incomplete" => Ok(Status::Open),
+            "done" | "complete" | "completed" => Ok(Status::Done),
+            "ready" => Ok(Status::Ready),
+            "blocked" => Ok(Status::Blocked),
+            "overdue" => Ok(Status::Overdue),
+            other => Err(TodoError::new_from_msg(format!(
+                "Unknown status '{}'. Expected open, done, ready, blocked or overdue.",
+                other
+            ))),
+        }
+    }
+}
+
+impl Comparison {
+    // Only called with the operators split_term recognizes, other than "~"
+    fn parse(operator: &str) -> Comparison {
+        match operator {
+            "<" => Comparison::Before,
+            "<=" => Comparison::OnOrBefore,
+            ">=" => Comparison::OnOrAfter,
+            ">" => Comparison::After,
+            _ => Comparison::On,
+        }
+    }
+
+    fn holds(&self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Before => ordering == Ordering::Less,
+            Comparison::OnOrBefore => ordering != Ordering::Greater,
+            Comparison::On => ordering == Ordering::Equal,
+            Comparison::OnOrAfter => ordering != Ordering::Less,
+            Comparison::After => ordering == Ordering::Greater,
+        }
+    }
+}
+
+impl SortKey {
+    // "due" sorts ascending and "-due" descending
+    fn parse(input: &str) -> Result<SortKey, TodoError> {
+        let (descending, field) = match input.strip_prefix('-') {
+            Some(field) => (true, field),
+            None => (false, input),
+        };
+        let field = match field.to_lowercase().as_str() {
+            "id" => SortField::Id,
+            "due" => SortField::Due,
+            "priority" => SortField::Priority,
+            "title" => SortField::Title,
+            other => SortField::Timestamp(Timestamp::parse(other).map_err(|_| {
+                TodoError::new_from_msg(format!(
+                    "Unknown sort field '{}'. Expected id, due, priority, title, created, completed or updated.",
+                    other
+                ))
+            })?),
+        };
+
+        Ok(SortKey { field, descending })
+    }
+
+    // Items without a due date or timestamp come last in either direction
+    fn compare(&self, a: &TodoItem, b: &TodoItem) -> Ordering {
+        let ordering = match self.field {
+            SortField::Id => a.id.cmp(&b.id),
+            SortField::Priority => a.priority_rank().cmp(&b.priority_rank()),
+            SortField::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            SortField::Due => return compare_present(a.deadline(), b.deadline(), self.descending),
+            SortField::Timestamp(timestamp) => {
+                return compare_present(a.timestamp(timestamp), b.timestamp(timestamp), self.descending)
+            }
+        };
+
+        match self.descending {
+            true => ordering.reverse(),
+            false => ordering,
+        }
+    }
+}
+
+fn compare_present<T: Ord>(a: Option<T>, b: Option<T>, descending: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if descending => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+// Split "field<op>value" at the first operator, or None for a plain word
+fn split_term(term: &str) -> Option<(&str, &'static str, &str)> {
+    let start = term.find(|c: char| "<>=:~".contains(c))?;
+    let operator = OPERATORS
+        .iter()
+        .find(|operator| term[start..].starts_with(*operator))?;
+
+    Some((&term[..start], operator, &term[start + operator.len()..]))
+}
+
+// Split the query at whitespace outside of double quotes. Quotes are removed, so
+// title~"deploy to prod" is a single token.
+fn tokenize(input: &str) -> Result<Vec<Token>, TodoError> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    let mut open_quote = None;
+
+    for (index, c) in input.char_indices() {
+        if c.is_whitespace() && open_quote.is_none() {
+            tokens.extend(current.take());
+            continue;
+        }
+
+        let token = current.get_or_insert(Token {
+            text: String::new(),
+            start: index,
+            length: 0,
+        });
+        token.length = index + c.len_utf8() - token.start;
+        match c {
+            '"' if open_quote.is_some() => open_quote = None,
+            '"' => open_quote = Some(index),
+            c => token.text.push(c),
+        }
+    }
+    if let Some(index) = open_quote {
+        return Err(point_at(input, index, 1, "Unterminated quote."));
+    }
+    tokens.extend(current);
+
+    Ok(tokens)
+}
+
+// An error message followed by the query with the offending part underlined
+fn point_at(input: &str, start: usize, length: usize, message: &str) -> TodoError {
+    TodoError::new_from_msg(format!(
+        "{}\n  {}\n  {}{}",
+        message,
+        input,
+        " ".repeat(input[..start].width()),
+        "^".repeat(input[start..start + length].width().max(1))
+    ))
+}
+
+#[cfg(test)]
+mod query_tests {
+    use super::*;
+
+    // A Wednesday
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 1, 18).unwrap()
+    }
+
+    #[test]
+    fn parse_terms() {
+        let query = Query::parse_relative_to(
+            r#"status:open due<2023-02-01 tag:ops title~"deploy to prod" -priority:low sort:-due,title"#,
+            today(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                Term {
+                    filter: Filter::Status(Status::Open),
+                    negated: false
+                },
+                Term {
+                    filter: Filter::Due(
+                        Comparison::Before,
+                        NaiveDate::from_ymd_opt(2023, 2, 1).unwrap()
+                    ),
+                    negated: false
+                },
+                Term {
+                    filter: Filter::Tag(String::from("ops")),
+                    negated: false
+                },
+                Term {
+                    filter: Filter::TitleContains(String::from("deploy to prod")),
+                    negated: false
+                },
+                Term {
+                    filter: Filter::Priority(Some(Priority::Low)),
+                    negated: true
+                },
+            ],
+            query.terms
+        );
+        assert_eq!(
+            vec![
+                SortKey {
+                    field: SortField::Due,
+                    descending: true
+                },
+                SortKey {
+                    field: SortField::Title,
+                    descending: false
+                },
+            ],
+            query.sort_keys
+        );
+    }
+
+    #[test]
+    fn relative_dates_and_plain_words() {
+        let query = Query::parse_relative_to("due<=+7d due:none Report", today()).unwrap();
+
+        assert_eq!(
+            vec![
+                Term {
+                    filter: Filter::Due(
+                        Comparison::OnOrBefore,
+                        NaiveDate::from_ymd_opt(2023, 1, 25).unwrap()
+                    ),
+                    negated: false
+                },
+                Term {
+                    filter: Filter::Undated,
+                    negated: false
+                },
+                Term {
+                    filter: Filter::TitleContains(String::from("report")),
+                    negated: false
+                },
+            ],
+            query.terms
+        );
+    }
+
+    #[test]
+    fn errors_point_at_bad_token() {
+        let error = Query::parse_relative_to("status:open stauts:done", today())
+            .err()
+            .unwrap();
+        assert_eq!(
+            format!(
+                "Unknown field 'stauts'. Expected {}.\n  status:open stauts:done\n              ^^^^^^^^^^^",
+                QUERY_FIELDS
+            ),
+            error.message
+        );
+
+        let error = Query::parse_relative_to("tag:ops due<2023-13-01", today())
+            .err()
+            .unwrap();
+        assert!(error.message.starts_with("Due date must be a valid date"));
+        assert!(error.message.ends_with("\n          ^^^^^^^^^^^^^^"));
+
+        let error = Query::parse_relative_to(r#"title~"deploy"#, today()).err().unwrap();
+        assert!(error.message.starts_with("Unterminated quote."));
+        assert!(error.message.ends_with("\n        ^"));
+
+        for query in ["tag~ops", "sort:size", "status:", "priority:urgent", "due<+4000000000d"] {
+            assert!(Query::parse_relative_to(query, today()).is_err(), "{}", query);
+        }
+    }
+}
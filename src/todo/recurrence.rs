@@ -0,0 +1,243 @@
+use crate::err::TodoError;
+use crate::todo::due_date::{add_days, day_in_month, parse_weekday};
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+// How a recurring item is rescheduled once it is completed. Rules are written and persisted as
+// short strings such as "daily", "weekly:mon,fri", "monthly:15" or "after:3d".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recurrence {
+    Daily,
+    // On the given weekdays, or a week after the due date if none are given
+    Weekly { weekdays: Vec<Weekday> },
+    // On the given day of the month, or on the day of the due date if none is given. Days past the
+    // end of a month fall on its last day.
+    Monthly { day: Option<u32> },
+    // A number of days after the item was completed, regardless of its due date
+    AfterCompletion { days: u32 },
+}
+
+// Longest wait allowed by an after:Nd rule, about ten years
+const MAX_DAYS_AFTER_COMPLETION: u32 = 3650;
+
+const WEEKDAYS: [(Weekday, &str); 7] = [
+    (Weekday::Mon, "mon"),
+    (Weekday::Tue, "tue"),
+    (Weekday::Wed, "wed"),
+    (Weekday::Thu, "thu"),
+    (Weekday::Fri, "fri"),
+    (Weekday::Sat, "sat"),
+    (Weekday::Sun, "sun"),
+];
+
+impl Recurrence {
+    pub fn parse(input: &str) -> Result<Recurrence, TodoError> {
+        let input = input.trim().trim_start_matches('*').to_lowercase();
+        let (rule, argument) = match input.split_once(':') {
+            Some((rule, argument)) => (rule, Some(argument)),
+            None => (input.as_str(), None),
+        };
+
+        match (rule, argument) {
+            ("daily", None) => Ok(Recurrence::Daily),
+            ("weekly", None) => Ok(Recurrence::Weekly { weekdays: Vec::new() }),
+            ("weekly", Some(weekdays)) => {
+                let mut parsed = weekdays
+                    .split(',')
+                    .map(|weekday| parse_weekday(weekday).ok_or_else(|| invalid_rule(&input)))
+                    .collect::<Result<Vec<Weekday>, TodoError>>()?;
+                parsed.sort_by_key(|weekday| weekday.num_days_from_monday());
+                parsed.dedup();
+
+                Ok(Recurrence::Weekly { weekdays: parsed })
+            }
+            ("monthly", None) => Ok(Recurrence::Monthly { day: None }),
+            ("monthly", Some(day)) => match day.parse::<u32>() {
+                Ok(day) if (1..=31).contains(&day) => Ok(Recurrence::Monthly { day: Some(day) }),
+                _ => Err(invalid_rule(&input)),
+            },
+            ("after", Some(days)) => match days.trim_end_matches('d').parse::<u32>() {
+                Ok(days) if (1..=MAX_DAYS_AFTER_COMPLETION).contains(&days) => {
+                    Ok(Recurrence::AfterCompletion { days })
+                }
+                _ => Err(invalid_rule(&input)),
+            },
+            _ => Err(invalid_rule(&input)),
+        }
+    }
+
+    // Like parse, but "none" removes the rule
+    pub fn parse_optional(input: &str) -> Result<Option<Recurrence>, TodoError> {
+        match input.trim().to_lowercase().as_str() {
+            "none" => Ok(None),
+            other => Recurrence::parse(other).map(Some),
+        }
+    }
+
+    // A monthly rule without a day takes the day of the given due date. Otherwise every short
+    // month would move the following occurrences to an earlier day for good.
+    pub fn pinned_to(&self, due_date: NaiveDate) -> Recurrence {
+        match self {
+            Recurrence::Monthly { day: None } => Recurrence::Monthly {
+                day: Some(due_date.day()),
+            },
+            other => other.clone(),
+        }
+    }
+
+    // Due date of the occurrence that follows one due on due_date and completed on completed_on.
+    // None if that date is out of range.
+    pub fn next_due_date(&self, due_date: NaiveDate, completed_on: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Recurrence::Daily => add_days(due_date, 1),
+            Recurrence::Weekly { weekdays } if weekdays.is_empty() => add_days(due_date, 7),
+            Recurrence::Weekly { weekdays } => (1..=7)
+                .filter_map(|days| add_days(due_date, days))
+                .find(|date| weekdays.contains(&date.weekday())),
+            Recurrence::Monthly { day } => {
+                let day = day.unwrap_or_else(|| due_date.day());
+                let this_month = day_in_month(due_date.year(), due_date.month(), day)?;
+                if this_month > due_date {
+                    return Some(this_month);
+                }
+                match due_date.month() {
+                    12 => day_in_month(due_date.year().checked_add(1)?, 1, day),
+                    month => day_in_month(due_date.year(), month + 1, day),
+                }
+            }
+            Recurrence::AfterCompletion { days } => add_days(completed_on, *days as i64),
+        }
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recurrence::Daily => write!(f, "daily"),
+            Recurrence::Weekly { weekdays } if weekdays.is_empty() => write!(f, "weekly"),
+            Recurrence::Weekly { weekdays } => write!(
+                f,
+                "weekly:{}",
+                weekdays
+                    .iter()
+                    .map(weekday_name)
+                    .collect::<Vec<&str>>()
+                    .join(",")
+            ),
+            Recurrence::Monthly { day: None } => write!(f, "monthly"),
+            Recurrence::Monthly { day: Some(day) } => write!(f, "monthly:{}", day),
+            Recurrence::AfterCompletion { days } => write!(f, "after:{}d", days),
+        }
+    }
+}
+
+impl Serialize for Recurrence {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Recurrence {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rule = String::deserialize(deserializer)?;
+
+        Recurrence::parse(&rule).map_err(|err| serde::de::Error::custom(err.message))
+    }
+}
+
+fn weekday_name(weekday: &Weekday) -> &'static str {
+    WEEKDAYS
+        .iter()
+        .find(|(candidate, _)| candidate == weekday)
+        .map(|(_, name)| *name)
+        .unwrap()
+}
+
+fn invalid_rule(input: &str) -> TodoError {
+    TodoError::new_from_msg(format!(
+        "Invalid repeat rule '{}'. Expected daily, weekly[:mon,...], monthly[:DAY] or after:Nd.",
+        input
+    ))
+}
+
+#[cfg(test)]
+mod recurrence_tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_display() {
+        for rule in ["daily", "weekly", "weekly:mon,fri", "monthly", "monthly:15", "after:3d"] {
+            assert_eq!(rule, Recurrence::parse(rule).unwrap().to_string());
+        }
+        assert_eq!(
+            Recurrence::Weekly {
+                weekdays: vec![Weekday::Mon, Weekday::Fri]
+            },
+            Recurrence::parse("*Weekly:fri,mon,fri").unwrap()
+        );
+        assert_eq!(None, Recurrence::parse_optional("none").unwrap());
+    }
+
+    #[test]
+    fn bad_rules() {
+        for rule in [
+            "hourly",
+            "daily:2",
+            "weekly:funday",
+            "monthly:32",
+            "after:0d",
+            "after",
+            "after:3651d",
+            "after:4000000000d",
+        ] {
+            let error = Recurrence::parse(rule).err().unwrap();
+            assert!(error.message.starts_with("Invalid repeat rule"), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn next_due_dates() {
+        let due_date = NaiveDate::from_ymd_opt(2023, 1, 31).unwrap(); // A Tuesday
+        let completed_on = NaiveDate::from_ymd_opt(2023, 2, 3).unwrap();
+        let next = |rule: &str| {
+            Recurrence::parse(rule).unwrap().next_due_date(due_date, completed_on).unwrap()
+        };
+
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 2, 1).unwrap(), next("daily"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 2, 7).unwrap(), next("weekly"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 2, 3).unwrap(), next("weekly:fri,mon"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 2, 6).unwrap(), next("weekly:mon"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 2, 28).unwrap(), next("monthly"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 2, 15).unwrap(), next("monthly:15"));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 2, 5).unwrap(), next("after:2d"));
+
+        let december = NaiveDate::from_ymd_opt(2023, 12, 20).unwrap();
+        let rule = Recurrence::parse("monthly:10").unwrap();
+        assert_eq!(
+            Some(NaiveDate::from_ymd_opt(2024, 1, 10).unwrap()),
+            rule.next_due_date(december, completed_on)
+        );
+    }
+
+    #[test]
+    fn monthly_keeps_its_day() {
+        let january = NaiveDate::from_ymd_opt(2023, 1, 31).unwrap();
+        let rule = Recurrence::parse("monthly").unwrap().pinned_to(january);
+        assert_eq!(Recurrence::Monthly { day: Some(31) }, rule);
+
+        let february = rule.next_due_date(january, january).unwrap();
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 2, 28).unwrap(), february);
+        assert_eq!(
+            Some(NaiveDate::from_ymd_opt(2023, 3, 31).unwrap()),
+            rule.next_due_date(february, february)
+        );
+    }
+
+    #[test]
+    fn out_of_range_next_due_date() {
+        let far_future = NaiveDate::from_ymd_opt(262_142, 12, 31).unwrap();
+        let rule = Recurrence::parse("after:3650d").unwrap();
+        assert_eq!(None, rule.next_due_date(far_future, far_future));
+    }
+}
@@ -1,115 +1,397 @@
 pub mod todo_store {
-    use serde::{Deserialize, Serialize};
-    use std::fs::File;
-    use std::io;
-    use std::io::{BufReader, Read, Write};
+    use chrono::{DateTime, NaiveDate, Utc};
+    use std::collections::{BTreeSet, HashSet};
 
     use crate::err::TodoError;
-    use crate::todo::{TodoItem, TodoItemSerializable};
+    use crate::todo::action::{ReversibleCommand, StoreAction, UndoHistory};
+    use crate::todo::backend::{JsonBackend, StorageBackend, StoreChange, StoreSnapshot};
+    use crate::todo::due_date;
+    use crate::todo::fuzzy::fuzzy_match;
+    use crate::todo::item::DEFAULT_MAX_TITLE_LENGTH;
+    use crate::todo::{
+        FuzzyMatch, ItemChanges, ItemInput, Project, Query, SavedView, Timestamp, TodoItem,
+        TodoItemSerializable,
+    };
 
-    #[derive(Debug, Serialize, Deserialize)]
-    struct TodoStoreSerializable {
-        store: Vec<TodoItemSerializable>,
-        next_id: usize,
+    // Number of unsnapshotted changes after which the backend is asked for a fresh snapshot
+    const SNAPSHOT_THRESHOLD: usize = 50;
+    // Number of commands kept on the undo stack
+    const UNDO_HISTORY_LIMIT: usize = 100;
+
+    // Which field the store is ordered by first. The other one breaks ties.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum SortOrder {
+        DueDate,
+        Priority,
+    }
+
+    impl SortOrder {
+        pub fn parse(input: &str) -> Result<SortOrder, TodoError> {
+            match input.trim().to_lowercase().as_str() {
+                "due" | "due-date" => Ok(SortOrder::DueDate),
+                "priority" => Ok(SortOrder::Priority),
+                other => Err(TodoError::new_from_msg(format!(
+                    "Unknown sort order '{}'. Expected due or priority.",
+                    other
+                ))),
+            }
+        }
+    }
+
+    // Where items without a due date go when sorting by due date
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum UndatedPosition {
+        First,
+        Last,
+    }
+
+    impl UndatedPosition {
+        pub fn parse(input: &str) -> Result<UndatedPosition, TodoError> {
+            match input.trim().to_lowercase().as_str() {
+                "first" => Ok(UndatedPosition::First),
+                "last" => Ok(UndatedPosition::Last),
+                other => Err(TodoError::new_from_msg(format!(
+                    "Unknown position '{}' for undated items. Expected first or last.",
+                    other
+                ))),
+            }
+        }
     }
 
     pub struct TodoStore {
         store: Vec<TodoItem>,
+        // Deleted items, kept until the trash is emptied so they can be restored
+        trash: Vec<TodoItem>,
+        projects: Vec<Project>,
+        views: Vec<SavedView>,
+        next_id: usize,
+        history: UndoHistory,
+        sort_order: SortOrder,
+        undated_position: UndatedPosition,
+        // Only enforced for new titles, so lowering it never invalidates existing items
+        max_title_length: usize,
+        // Extra columns shown when printing items
+        timestamp_columns: Vec<Timestamp>,
+        backend: Box<dyn StorageBackend>,
+    }
+
+    // Everything a change can touch, kept while the change is applied and persisted
+    struct Checkpoint {
+        store: Vec<TodoItem>,
+        trash: Vec<TodoItem>,
+        projects: Vec<Project>,
+        views: Vec<SavedView>,
         next_id: usize,
-        persistence_filepath: String
+        history: UndoHistory,
     }
 
     impl TodoStore {
         pub fn new_from_persistence(filepath: &str) -> Result<TodoStore, TodoError> {
-            // Open and read persistence store file contents
-            let persistence_store = File::options()
-                .read(true)
-                .create(true)
-                .write(true)
-                .open(filepath)
-                .unwrap();
-
-            let mut persistence_store_contents = String::new();
-            BufReader::new(persistence_store)
-                .read_to_string(&mut persistence_store_contents)
-                .unwrap();
+            TodoStore::new_from_backend(Box::new(JsonBackend::open(filepath)?))
+        }
 
-            // Deserialize persistence store
-            let store_dto: TodoStoreSerializable =
-                serde_json::from_str(&persistence_store_contents).map_err(|err| {
-                    TodoError::new(
-                        String::from("Error reading persistence file. Data is likely corrupted."),
-                        Box::new(err),
-                    )
-                })?;
+        pub fn new_from_backend(mut backend: Box<dyn StorageBackend>) -> Result<TodoStore, TodoError> {
+            let loaded = backend.load()?;
 
             // Create TodoItems from TodoItemSerializables
-            let todo_items: Vec<TodoItem> = store_dto
-                .store
+            let todo_items: Vec<TodoItem> = loaded
+                .snapshot
+                .items
                 .into_iter()
-                .map(|item| TodoItem::deserialize(item))
+                .map(TodoItem::deserialize)
+                .collect::<Result<Vec<TodoItem>, TodoError>>()?;
+            let trash: Vec<TodoItem> = loaded
+                .snapshot
+                .trash
+                .into_iter()
+                .map(TodoItem::deserialize)
                 .collect::<Result<Vec<TodoItem>, TodoError>>()?;
 
-            // Calculate longest title length
-            let mut longest_title_length = 0;
-            todo_items.iter().for_each(|item| {
-                if item.title.len() > longest_title_length {
-                    longest_title_length = item.title.len();
-                }
-            });
-
-            Ok(TodoStore {
-                next_id: store_dto.next_id,
+            let mut store = TodoStore {
+                next_id: loaded.snapshot.next_id,
                 store: todo_items,
-                persistence_filepath: String::from(filepath)
+                trash,
+                projects: loaded.snapshot.projects,
+                views: loaded.snapshot.views,
+                history: loaded.snapshot.history,
+                sort_order: SortOrder::DueDate,
+                undated_position: UndatedPosition::Last,
+                max_title_length: DEFAULT_MAX_TITLE_LENGTH,
+                timestamp_columns: Vec::new(),
+                backend,
+            };
+            store.sort_store();
+
+            // Recover any actions the backend recorded but never folded into its snapshot
+            for action in loaded.pending_actions {
+                store.execute(action)?;
+            }
+            if loaded.needs_snapshot {
+                store.snapshot()?;
+            }
+
+            Ok(store)
+        }
+
+        pub fn add(&mut self, due_date: NaiveDate, title: &str) -> Result<&TodoItem, TodoError> {
+            self.create(ItemInput::new(due_date, title))
+        }
+
+        // Reads new item input the way TodoItem::parse_input does, except that "@name" is only
+        // taken as a project marker when the project exists
+        pub fn parse_input(&self, args: &str) -> Result<ItemInput, TodoError> {
+            TodoItem::parse_input_with_projects(args, due_date::today(), |name| {
+                self.get_project(name).is_ok()
             })
         }
 
-        pub fn create_new_todo(&mut self) -> Result<(), TodoError> {
-            println!("Enter a new Todo Item or return to [m]enu:");
-            println!("Format: YYYY-MM-DD {{Title}}");
-            print!("> ");
-            io::stdout().flush().unwrap();
+        pub fn create(&mut self, mut input: ItemInput) -> Result<&TodoItem, TodoError> {
+            input.project = input
+                .project
+                .map(|name| self.resolve_open_project(&name))
+                .transpose()?;
+            if let Some(parent_id) = input.parent {
+                self.check_parent(None, parent_id)?;
+            }
+            self.check_blockers(None, &input.blocked_by)?;
+            TodoItem::validate_title_length(&input.title, self.max_title_length)?;
+            let item = TodoItem::from_input(self.next_id, input)?;
+            let id = item.id;
 
-            let mut new_todo = String::new();
-            io::stdin().read_line(&mut new_todo).map_err(|err| {
-                TodoError::new(String::from("Failed to read line."), Box::new(err))
+            self.commit(StoreAction::Create {
+                item: TodoItemSerializable::from(&item),
             })?;
 
-            self.add_item(TodoItem::new(new_todo, self.next_id)?);
-            Ok(())
+            self.get_item(id)
         }
 
-        pub fn mark_as_done(&mut self) -> Result<(), TodoError> {
-            println!("Enter the ID of the completed item or return to [m]enu:");
-            print!("> ");
-            io::stdout().flush().unwrap();
+        // Complete an item. Items that are blocked or have open subtasks can only be completed with
+        // force_complete.
+        pub fn complete(&mut self, id: usize) -> Result<(), TodoError> {
+            let open_blockers = self.open_blockers(self.get_item(id)?);
+            if !open_blockers.is_empty() {
+                return Err(TodoError::new_from_msg(format!(
+                    "Item {} is blocked by open item(s) {}. Complete them first or force completion.",
+                    id,
+                    join_ids(&open_blockers)
+                )));
+            }
 
-            // Get user input
-            let mut completed_todo_id = String::new();
-            io::stdin()
-                .read_line(&mut completed_todo_id)
-                .map_err(|err| {
-                    TodoError::new(String::from("Failed to read line."), Box::new(err))
-                })?;
+            let open_subtask_count = self.open_subtasks(id)?.len();
+            if open_subtask_count > 0 {
+                return Err(TodoError::new_from_msg(format!(
+                    "Item {} has {} open subtask(s). Complete them first or force completion.",
+                    id, open_subtask_count
+                )));
+            }
+
+            self.commit_completion(id, Vec::new())
+        }
+
+        // Complete an item along with all of its open subtasks, whether or not it is blocked
+        pub fn force_complete(&mut self, id: usize) -> Result<(), TodoError> {
+            let open_subtasks = self.open_subtasks(id)?;
+
+            self.commit_completion(id, open_subtasks)
+        }
+
+        // Flip a completed item back to incomplete. Completed parents are reopened with it since a
+        // parent is only complete while all of its subtasks are.
+        pub fn reopen(&mut self, id: usize) -> Result<(), TodoError> {
+            if !self.get_item(id)?.complete {
+                return Err(TodoError::new_from_msg(String::from(
+                    "Only completed items can be reopened.",
+                )));
+            }
+
+            let at = Some(Utc::now());
+            let mut actions = vec![StoreAction::Reopen { id, at }];
+            for ancestor in self.ancestors(id) {
+                if !ancestor.complete {
+                    break;
+                }
+                actions.push(StoreAction::Reopen { id: ancestor.id, at });
+            }
+
+            self.commit(batch(actions))
+        }
+
+        // Change any of the user editable fields of an item
+        pub fn edit(&mut self, id: usize, mut changes: ItemChanges) -> Result<&TodoItem, TodoError> {
+            if changes.is_empty() {
+                return Err(TodoError::new_from_msg(String::from(
+                    "A new title, due date, priority, tags, project, parent, blockers or repeat rule must be provided.",
+                )));
+            }
+            if let Some(title) = &changes.title {
+                TodoItem::validate_title_length(title, self.max_title_length)?;
+            }
+            if let Some(Some(name)) = &changes.project {
+                changes.project = Some(Some(self.resolve_open_project(name)?));
+            }
+            if let Some(Some(parent_id)) = changes.parent {
+                self.check_parent(Some(id), parent_id)?;
+            }
+            if let Some(blocked_by) = &changes.blocked_by {
+                self.check_blockers(Some(id), blocked_by)?;
+            }
+
+            let mut item = self.get_item(id)?.clone();
+            item.update(changes)?;
+
+            self.commit(StoreAction::Edit {
+                item: TodoItemSerializable::from(&item),
+            })?;
+
+            self.get_item(id)
+        }
+
+        // Move an item into a project, or out of its project when given None
+        pub fn move_item(&mut self, id: usize, project: Option<&str>) -> Result<&TodoItem, TodoError> {
+            self.edit(
+                id,
+                ItemChanges {
+                    project: Some(project.map(String::from)),
+                    ..ItemChanges::default()
+                },
+            )
+        }
+
+        pub fn create_project(&mut self, name: &str) -> Result<&Project, TodoError> {
+            let project = Project::new(name)?;
+            if self.get_project(&project.name).is_ok() {
+                return Err(TodoError::new_from_msg(format!(
+                    "A project named '{}' already exists.",
+                    project.name
+                )));
+            }
+            let name = project.name.clone();
+            self.commit(StoreAction::CreateProject { project })?;
+
+            self.get_project(&name)
+        }
 
-            // Validate and parse ID
-            let completed_todo_id = completed_todo_id.trim().parse::<usize>().map_err(|err| {
-                TodoError::new(String::from("Input must be an ID."), Box::new(err))
+        pub fn rename_project(&mut self, name: &str, new_name: &str) -> Result<&Project, TodoError> {
+            let name = self.get_project(name)?.name.clone();
+            let new_name = Project::parse_name(new_name)?;
+            // Changing only the case of a name is allowed
+            if !new_name.eq_ignore_ascii_case(&name) && self.get_project(&new_name).is_ok() {
+                return Err(TodoError::new_from_msg(format!(
+                    "A project named '{}' already exists.",
+                    new_name
+                )));
+            }
+            self.commit(StoreAction::RenameProject {
+                name,
+                new_name: new_name.clone(),
             })?;
 
-            // Mark specified item as complete
+            self.get_project(&new_name)
+        }
+
+        // Hide a project and its items from the regular lists. Its items stay reachable by ID and
+        // through the project view.
+        pub fn archive_project(&mut self, name: &str) -> Result<(), TodoError> {
+            let project = self.get_project(name)?;
+            if project.archived {
+                return Err(TodoError::new_from_msg(format!(
+                    "Project '{}' is already archived.",
+                    project.name
+                )));
+            }
+            let name = project.name.clone();
+
+            self.commit(StoreAction::ArchiveProject { name })
+        }
+
+        pub fn unarchive_project(&mut self, name: &str) -> Result<(), TodoError> {
+            let project = self.get_project(name)?;
+            if !project.archived {
+                return Err(TodoError::new_from_msg(format!(
+                    "Project '{}' is not archived.",
+                    project.name
+                )));
+            }
+            let name = project.name.clone();
+
+            self.commit(StoreAction::UnarchiveProject { name })
+        }
+
+        // Move an item to the trash. It can be brought back with restore until the trash is emptied.
+        pub fn delete(&mut self, id: usize) -> Result<(), TodoError> {
+            self.get_item(id)?;
+
+            self.commit(StoreAction::Delete { id })
+        }
+
+        pub fn restore(&mut self, id: usize) -> Result<&TodoItem, TodoError> {
+            self.get_trashed_item(id)?;
+            self.commit(StoreAction::Restore { id })?;
+
+            self.get_item(id)
+        }
+
+        // Permanently remove every item in the trash. Returns the number of items removed.
+        pub fn empty_trash(&mut self) -> Result<usize, TodoError> {
+            let trashed_item_count = self.trash.len();
+            if trashed_item_count > 0 {
+                self.commit(StoreAction::EmptyTrash)?;
+            }
+
+            Ok(trashed_item_count)
+        }
+
+        // Revert the most recent change. Returns the action that was undone.
+        pub fn undo(&mut self) -> Result<StoreAction, TodoError> {
+            let command = self.history.undo.last().ok_or(TodoError::new_from_msg(
+                String::from("Nothing to undo."),
+            ))?;
+            let action = command.action.clone();
+            self.commit(StoreAction::Undo)?;
+
+            Ok(action)
+        }
+
+        // Re-apply the most recently undone change. Returns the action that was redone.
+        pub fn redo(&mut self) -> Result<StoreAction, TodoError> {
+            let command = self.history.redo.last().ok_or(TodoError::new_from_msg(
+                String::from("Nothing to redo."),
+            ))?;
+            let action = command.action.clone();
+            self.commit(StoreAction::Redo)?;
+
+            Ok(action)
+        }
+
+        pub fn sort_by(&mut self, sort_order: SortOrder) {
+            self.sort_order = sort_order;
+            self.sort_store();
+        }
+
+        pub fn place_undated(&mut self, undated_position: UndatedPosition) {
+            self.undated_position = undated_position;
+            self.sort_store();
+        }
+
+        pub fn set_max_title_length(&mut self, max_title_length: usize) {
+            self.max_title_length = max_title_length;
+        }
+
+        pub fn show_timestamps(&mut self, timestamp_columns: Vec<Timestamp>) {
+            self.timestamp_columns = timestamp_columns;
+        }
+
+        pub fn timestamp_columns(&self) -> &[Timestamp] {
+            &self.timestamp_columns
+        }
+
+        pub fn get_item(&self, id: usize) -> Result<&TodoItem, TodoError> {
             self.store
-                .iter_mut()
-                .find(|item| item.id == completed_todo_id)
+                .iter()
+                .find(|item| item.id == id)
                 .ok_or(TodoError::new_from_msg(String::from(
                     "Please select a valid ID.",
-                )))?
-                .mark_as_done();
-
-            self.persist_data();
-
-            Ok(())
+                )))
         }
 
         pub fn list_all_todos(&self) -> Vec<&TodoItem> {
@@ -120,194 +402,2616 @@ pub mod todo_store {
             self.get_filtered_store(|item: &&TodoItem| !item.complete)
         }
 
+        // Most recently completed first. Items completed before completion times were recorded
+        // come last.
         pub fn list_history(&self) -> Vec<&TodoItem> {
-            self.get_filtered_store(|item: &&TodoItem| item.complete)
+            let mut history = self.get_filtered_store(|item: &&TodoItem| item.complete);
+            history.sort_by_key(|item| std::cmp::Reverse(item.completed_at));
+
+            history
         }
 
-        fn add_item(&mut self, new_item: TodoItem) {
+        pub fn get_project(&self, name: &str) -> Result<&Project, TodoError> {
+            self.projects
+                .iter()
+                .find(|project| project.is_named(name))
+                .ok_or(TodoError::new_from_msg(format!(
+                    "There is no project named '{}'.",
+                    name.trim()
+                )))
+        }
 
-            self.store.push(new_item);
-            self.sort_store();
-            self.next_id += 1;
-            self.persist_data();
+        pub fn list_projects(&self) -> Vec<&Project> {
+            self.projects.iter().collect()
         }
 
-        fn sort_store(&mut self) {
-            self.store.sort_by(|a, b| a.due_date.cmp(&b.due_date))
+        // Save a view, replacing the one with the same name if there is one
+        pub fn save_view(&mut self, view: SavedView) -> Result<&SavedView, TodoError> {
+            let name = view.name.clone();
+            self.commit(StoreAction::SaveView { view })?;
+
+            self.get_view(&name)
         }
 
-        // TODO: Think about ways to optimize this.. Can we append data? How do we edit existing data?
-        //   Maybe I can create a living file of appended "actions". On quit, the store is persisted and
-        //   the action list is deleted. If on startup, that file exists, recreate the state
-        fn persist_data(&self) {
-            let store: Vec<TodoItemSerializable> =
-                self.store.iter().map(TodoItemSerializable::from).collect();
+        pub fn delete_view(&mut self, name: &str) -> Result<(), TodoError> {
+            let name = self.get_view(name)?.name.clone();
 
-            let store_dto = TodoStoreSerializable {
-                next_id: self.next_id,
-                store,
-            };
+            self.commit(StoreAction::RemoveView { name })
+        }
 
-            let store_dto_json = serde_json::to_string_pretty(&store_dto).unwrap();
-            let mut persistence = File::create(&self.persistence_filepath).unwrap();
-            persistence.write_all(store_dto_json.as_bytes()).unwrap();
+        pub fn get_view(&self, name: &str) -> Result<&SavedView, TodoError> {
+            self.views
+                .iter()
+                .find(|view| view.is_named(name))
+                .ok_or(TodoError::new_from_msg(format!(
+                    "There is no saved view named '{}'.",
+                    name.trim()
+                )))
         }
 
-        fn get_filtered_store<F>(&self, filter: F) -> Vec<&TodoItem>
-        where
-            F: FnMut(&&TodoItem) -> bool, // TODO: Is a double reference necessary?
-        {
-            self.store.iter().filter(filter).by_ref().collect()
+        // In the order they were first saved
+        pub fn list_views(&self) -> Vec<&SavedView> {
+            self.views.iter().collect()
         }
-    }
-}
 
-pub mod todo_printer {
-    use crate::TodoItem;
+        // Every item in a project, including when the project is archived
+        pub fn list_project(&self, name: &str) -> Result<Vec<&TodoItem>, TodoError> {
+            let name = &self.get_project(name)?.name;
 
-    #[derive(Debug, Clone)]
-    enum Justification {
-        Left,
-        Right,
-    }
+            Ok(self
+                .store
+                .iter()
+                .filter(|item| item.project.as_ref() == Some(name))
+                .collect())
+        }
 
-    type DataSupplier = dyn Fn(&TodoItem) -> String;
+        // Direct subtasks of an item
+        pub fn list_subtasks(&self, id: usize) -> Vec<&TodoItem> {
+            self.store
+                .iter()
+                .filter(|item| item.parent == Some(id))
+                .collect()
+        }
 
-    struct TableColumn {
-        pub header: &'static str,
-        pub justification: Justification,
-        pub data_supplier: Box<DataSupplier>,
-        pub width: usize,
-    }
+        // Number of completed and total direct subtasks, or None if the item has none
+        pub fn subtask_progress(&self, id: usize) -> Option<(usize, usize)> {
+            let subtasks = self.list_subtasks(id);
+            if subtasks.is_empty() {
+                return None;
+            }
+            let completed_count = subtasks.iter().filter(|item| item.complete).count();
 
-    impl TableColumn {
-        fn new(
-            header: &'static str,
-            justification: Justification,
-            data_supplier: Box<DataSupplier>,
-        ) -> TableColumn {
-            TableColumn {
-                header,
-                justification,
-                data_supplier,
-                width: header.chars().count(),
+            Some((completed_count, subtasks.len()))
+        }
+
+        // Blockers of an item that are still open. Blockers that were deleted no longer count.
+        pub fn open_blockers(&self, item: &TodoItem) -> Vec<usize> {
+            item.blocked_by
+                .iter()
+                .copied()
+                .filter(|id| self.get_item(*id).is_ok_and(|blocker| !blocker.complete))
+                .collect()
+        }
+
+        pub fn is_blocked(&self, item: &TodoItem) -> bool {
+            !self.open_blockers(item).is_empty()
+        }
+
+        // Incomplete items that are not waiting on anything
+        pub fn list_ready(&self) -> Vec<&TodoItem> {
+            self.get_filtered_store(|item: &&TodoItem| !item.complete && !self.is_blocked(item))
+        }
+
+        // Items matching the query, in the order it asks for
+        pub fn search(&self, query: &Query) -> Vec<&TodoItem> {
+            let now = Utc::now();
+            let mut items = self.get_filtered_store(|item: &&TodoItem| query.matches(item, self, now));
+            query.sort(&mut items);
+
+            items
+        }
+
+        // Items whose titles fuzzy match the text, best match first. Equal scores keep the store
+        // order.
+        pub fn find(&self, text: &str) -> Vec<(&TodoItem, FuzzyMatch)> {
+            let mut matches: Vec<(&TodoItem, FuzzyMatch)> = self
+                .list_all_todos()
+                .into_iter()
+                .filter_map(|item| fuzzy_match(text, &item.title).map(|found| (item, found)))
+                .collect();
+            matches.sort_by_key(|(_, found)| std::cmp::Reverse(found.score));
+
+            matches
+        }
+
+        // Incomplete items whose deadline is before now
+        pub fn list_overdue(&self, now: DateTime<Utc>) -> Vec<&TodoItem> {
+            self.get_filtered_store(|item: &&TodoItem| item.is_overdue(now))
+        }
+
+        pub fn list_tagged(&self, tag: &str) -> Vec<&TodoItem> {
+            self.get_filtered_store(|item: &&TodoItem| item.has_tag(tag))
+        }
+
+        pub fn list_trash(&self) -> Vec<&TodoItem> {
+            self.trash.iter().collect()
+        }
+
+        // Fold any changes the backend is still holding separately into its snapshot. Called on a
+        // clean quit.
+        pub fn compact(&mut self) -> Result<(), TodoError> {
+            if self.backend.pending_change_count() > 0 {
+                self.snapshot()?;
             }
+
+            Ok(())
         }
-    }
 
-    fn get_columns() -> Vec<TableColumn> {
-        vec![
-            TableColumn::new(
-                "#",
-                Justification::Right,
-                Box::new(|item| item.id.to_string()),
-            ),
-            TableColumn::new(
-                "√",
-                Justification::Left,
-                Box::new(|item| match item.complete {
-                    true => String::from("X"),
-                    false => String::from(" "),
-                }),
-            ),
-            TableColumn::new(
-                "Date due",
-                Justification::Left,
-                Box::new(|item| item.due_date.to_string()),
-            ),
-            TableColumn::new(
-                "Title",
-                Justification::Left,
-                Box::new(|item| item.title.to_string()),
-            ),
-        ]
-    }
+        // Nothing changes in memory unless the backend has recorded the change, so a failed write
+        // can never be picked up by a later snapshot
+        fn commit(&mut self, action: StoreAction) -> Result<(), TodoError> {
+            let checkpoint = self.checkpoint();
+            let result = self.execute_and_persist(action);
+            if result.is_err() {
+                self.restore_checkpoint(checkpoint);
+            }
+            result?;
 
-    pub fn print_store(data_title: &str, collection: &[&TodoItem]) {
-        // Get max width of each column
-        let mut table_columns = get_columns();
+            // The change itself is safely recorded by now, so a failed snapshot only means the
+            // backend holds on to its changes a little longer and tries again on the next commit
+            if self.backend.pending_change_count() >= SNAPSHOT_THRESHOLD {
+                if let Err(err) = self.snapshot() {
+                    eprintln!("Warning: could not compact the store: {}", err);
+                }
+            }
 
-        // Set column widths
-        collection.iter().for_each(|item| {
-            for i in 0..table_columns.len() {
-                let item_data_at_column = (table_columns[i].data_supplier)(item);
-                if item_data_at_column.len() > table_columns[i].width {
-                    table_columns[i].width = item_data_at_column.len();
+            Ok(())
+        }
+
+        fn execute_and_persist(&mut self, action: StoreAction) -> Result<(), TodoError> {
+            let touched_ids = self.execute(action.clone())?;
+
+            // Report where every touched item ended up so the backend can persist just those
+            let mut change = StoreChange {
+                action: &action,
+                items: Vec::new(),
+                trashed: Vec::new(),
+                removed_ids: Vec::new(),
+                projects: &self.projects,
+                views: &self.views,
+                next_id: self.next_id,
+                history: &self.history,
+            };
+            for id in touched_ids {
+                if let Ok(item) = self.get_item(id) {
+                    change.items.push(TodoItemSerializable::from(item));
+                } else if let Ok(item) = self.get_trashed_item(id) {
+                    change.trashed.push(TodoItemSerializable::from(item));
+                } else {
+                    change.removed_ids.push(id);
                 }
             }
-        });
 
-        // Print all data
-        print_title(data_title, &table_columns);
-        print_header_rows(&table_columns);
-        collection
-            .iter()
-            .for_each(|item| print_table_row(&table_columns, item));
-    }
+            self.backend.apply_change(&change)
+        }
 
-    fn print_title(data_title: &str, columns: &[TableColumn]) {
-        // Calculate the total width of the table
-        let table_column_width_sum: usize = columns.iter().map(|column| column.width).sum();
-        let total_table_width = table_column_width_sum +
-            (columns.len() * 2) + // Padding chars
-            (columns.len() - 1); // Dividers
+        fn checkpoint(&self) -> Checkpoint {
+            Checkpoint {
+                store: self.store.clone(),
+                trash: self.trash.clone(),
+                projects: self.projects.clone(),
+                views: self.views.clone(),
+                next_id: self.next_id,
+                history: self.history.clone(),
+            }
+        }
 
-        // Calculate padding char count for left and right padding
-        let total_padding_char_count = total_table_width - (data_title.len() + 2);
-        let left_padding_length = total_padding_char_count / 2;
-        // If total_padding_char_count is odd, add an extra char to the right padding
-        let right_padding_length = left_padding_length + (total_padding_char_count % 2);
+        fn restore_checkpoint(&mut self, checkpoint: Checkpoint) {
+            self.store = checkpoint.store;
+            self.trash = checkpoint.trash;
+            self.projects = checkpoint.projects;
+            self.views = checkpoint.views;
+            self.next_id = checkpoint.next_id;
+            self.history = checkpoint.history;
+        }
 
-        // Create left and right padding strings
-        let left_padding = String::from("=").repeat(left_padding_length);
-        let right_padding = String::from("=").repeat(right_padding_length);
+        // Apply an action and keep the undo history in step with it. Live changes and journal replay
+        // both come through here so the rebuilt history always matches the original one.
+        fn execute(&mut self, action: StoreAction) -> Result<Vec<usize>, TodoError> {
+            match action {
+                StoreAction::Undo => {
+                    let command = self.history.undo.pop().ok_or(TodoError::new_from_msg(
+                        String::from("Nothing to undo."),
+                    ))?;
+                    // Put the command back if it cannot be applied so the history stays intact
+                    match self.apply(command.inverse.clone()) {
+                        Ok(touched_ids) => {
+                            self.history.redo.push(command);
+                            Ok(touched_ids)
+                        }
+                        Err(err) => {
+                            self.history.undo.push(command);
+                            Err(err)
+                        }
+                    }
+                }
+                StoreAction::Redo => {
+                    let command = self.history.redo.pop().ok_or(TodoError::new_from_msg(
+                        String::from("Nothing to redo."),
+                    ))?;
+                    match self.apply(command.action.clone()) {
+                        Ok(touched_ids) => {
+                            self.history.undo.push(command);
+                            Ok(touched_ids)
+                        }
+                        Err(err) => {
+                            self.history.redo.push(command);
+                            Err(err)
+                        }
+                    }
+                }
+                action => {
+                    let (inverse, touched_ids) = self.apply_with_inverse(action.clone())?;
 
-        // Print title
-        println!("{} {} {}", left_padding, data_title, right_padding);
-    }
+                    self.history.undo.push(ReversibleCommand { action, inverse });
+                    if self.history.undo.len() > UNDO_HISTORY_LIMIT {
+                        self.history.undo.remove(0);
+                    }
+                    self.history.redo.clear();
 
-    fn print_header_rows(columns: &[TableColumn]) {
-        // Initialize header and divider cells
-        let mut header_cells: Vec<String> = Vec::new();
-        let mut divider_cells: Vec<String> = Vec::new();
+                    Ok(touched_ids)
+                }
+            }
+        }
 
-        // For every TableColumn, create a left-justified string representing each header cell and
-        // a divider with the length of the column
-        columns.iter().for_each(|column| {
-            let header_right_padding_char_count = column.width - column.header.chars().count();
-            header_cells.push(format!(
-                " {}{} ",
-                column.header,
-                String::from(" ").repeat(header_right_padding_char_count)
-            ));
-            divider_cells.push(format!("-{}-", String::from("-").repeat(column.width)));
-        });
+        // Apply an action and return the action that reverts it along with the touched item ids. Each
+        // action in a batch sees the state left by the ones before it, so its inverse is captured
+        // just before it is applied.
+        fn apply_with_inverse(
+            &mut self,
+            action: StoreAction,
+        ) -> Result<(StoreAction, Vec<usize>), TodoError> {
+            match action {
+                StoreAction::Batch { actions } => {
+                    let mut inverses = Vec::new();
+                    let mut touched_ids = Vec::new();
+                    for action in actions {
+                        let (inverse, ids) = self.apply_with_inverse(action)?;
+                        inverses.push(inverse);
+                        touched_ids.extend(ids);
+                    }
+                    inverses.reverse();
 
-        // Print out all cells separated by a pipe
-        println!("{}", header_cells.join("|"));
-        println!("{}", divider_cells.join("|"));
-    }
+                    Ok((StoreAction::Batch { actions: inverses }, touched_ids))
+                }
+                action => {
+                    let inverse = self.inverse_of(&action)?;
+                    let touched_ids = self.apply(action)?;
 
-    fn print_table_row(columns: &[TableColumn], item: &TodoItem) {
-        // Create a string representing the TodoItem data for each column
-        let table_row = columns
-            .iter()
-            .map(|column| {
-                let data: String = (column.data_supplier)(item);
-                let padding_char_count = column.width - data.chars().count();
+                    Ok((inverse, touched_ids))
+                }
+            }
+        }
 
-                match column.justification {
-                    Justification::Left => {
-                        format!(" {}{} ", data, String::from(" ").repeat(padding_char_count))
+        // Build the action that reverts the given one from the current state of the store
+        fn inverse_of(&self, action: &StoreAction) -> Result<StoreAction, TodoError> {
+            Ok(match action {
+                StoreAction::Create { item } => StoreAction::Remove { id: item.id },
+                // Restores the timestamps as well as the completion state
+                StoreAction::Complete { id, .. } | StoreAction::Reopen { id, .. } => {
+                    StoreAction::Edit {
+                        item: TodoItemSerializable::from(self.get_item(*id)?),
+                    }
+                }
+                StoreAction::Edit { item } => StoreAction::Edit {
+                    item: TodoItemSerializable::from(self.get_item(item.id)?),
+                },
+                StoreAction::LegacyEdit { id, .. } => StoreAction::Edit {
+                    item: TodoItemSerializable::from(self.get_item(*id)?),
+                },
+                StoreAction::Delete { id } => StoreAction::Restore { id: *id },
+                StoreAction::Restore { id } => StoreAction::Delete { id: *id },
+                StoreAction::EmptyTrash => StoreAction::ReturnToTrash {
+                    items: self.trash.iter().map(TodoItemSerializable::from).collect(),
+                },
+                StoreAction::Remove { id } => StoreAction::Create {
+                    item: TodoItemSerializable::from(self.get_item(*id)?),
+                },
+                StoreAction::ReturnToTrash { .. } => StoreAction::EmptyTrash,
+                StoreAction::CreateProject { project } => StoreAction::RemoveProject {
+                    name: project.name.clone(),
+                },
+                StoreAction::RemoveProject { name } => StoreAction::CreateProject {
+                    project: self.get_project(name)?.clone(),
+                },
+                StoreAction::SaveView { view } => match self.get_view(&view.name) {
+                    Ok(existing) => StoreAction::SaveView {
+                        view: existing.clone(),
                     },
-                    Justification::Right => {
-                        format!(" {}{} ", String::from(" ").repeat(padding_char_count), data)
+                    Err(_) => StoreAction::RemoveView {
+                        name: view.name.clone(),
                     },
+                },
+                StoreAction::RemoveView { name } => StoreAction::SaveView {
+                    view: self.get_view(name)?.clone(),
+                },
+                StoreAction::RenameProject { name, new_name } => StoreAction::RenameProject {
+                    name: new_name.clone(),
+                    new_name: name.clone(),
+                },
+                StoreAction::ArchiveProject { name } | StoreAction::UnarchiveProject { name } => {
+                    match self.get_project(name)?.archived {
+                        true => StoreAction::ArchiveProject { name: name.clone() },
+                        false => StoreAction::UnarchiveProject { name: name.clone() },
+                    }
+                }
+                StoreAction::Batch { .. } => {
+                    return Err(TodoError::new_from_msg(String::from(
+                        "The inverse of a batch is built as it is applied.",
+                    )))
+                }
+                StoreAction::Undo | StoreAction::Redo => {
+                    return Err(TodoError::new_from_msg(String::from(
+                        "Undo and redo cannot themselves be undone.",
+                    )))
+                }
+            })
+        }
+
+        // Apply an action to the in-memory store and return the ids of every item it touched
+        fn apply(&mut self, action: StoreAction) -> Result<Vec<usize>, TodoError> {
+            match action {
+                StoreAction::Create { item } => {
+                    let item = TodoItem::deserialize(item)?;
+                    let id = item.id;
+                    self.next_id = self.next_id.max(id + 1);
+                    self.store.push(item);
+                    self.sort_store();
+
+                    Ok(vec![id])
+                }
+                StoreAction::Complete { id, at } => {
+                    self.get_item_mut(id)?.mark_as_done(at);
+
+                    Ok(vec![id])
+                }
+                StoreAction::Reopen { id, at } => {
+                    self.get_item_mut(id)?.reopen(at);
+
+                    Ok(vec![id])
+                }
+                StoreAction::Edit { item } => {
+                    let item = TodoItem::deserialize(item)?;
+                    let id = item.id;
+                    *self.get_item_mut(id)? = item;
+                    self.sort_store();
+
+                    Ok(vec![id])
+                }
+                StoreAction::LegacyEdit { id, title, due_date } => {
+                    TodoItem::validate_title(&title)?;
+                    let due_date = due_date::parse(&due_date, due_date::today())?;
+                    let item = self.get_item_mut(id)?;
+                    item.title = title;
+                    item.due_date = Some(due_date);
+                    self.sort_store();
+
+                    Ok(vec![id])
+                }
+                StoreAction::Delete { id } => {
+                    let index = self.store.iter().position(|item| item.id == id).ok_or(
+                        TodoError::new_from_msg(String::from("Please select a valid ID.")),
+                    )?;
+                    let item = self.store.remove(index);
+                    self.trash.push(item);
+
+                    Ok(vec![id])
+                }
+                StoreAction::Restore { id } => {
+                    let index = self.trash.iter().position(|item| item.id == id).ok_or(
+                        TodoError::new_from_msg(String::from(
+                            "Please select the ID of an item in the trash.",
+                        )),
+                    )?;
+                    let item = self.trash.remove(index);
+                    self.store.push(item);
+                    self.sort_store();
+
+                    Ok(vec![id])
+                }
+                StoreAction::EmptyTrash => {
+                    Ok(self.trash.drain(..).map(|item| item.id).collect())
+                }
+                StoreAction::Remove { id } => {
+                    let index = self.store.iter().position(|item| item.id == id).ok_or(
+                        TodoError::new_from_msg(String::from("Please select a valid ID.")),
+                    )?;
+                    self.store.remove(index);
+
+                    Ok(vec![id])
+                }
+                StoreAction::ReturnToTrash { items } => {
+                    let mut touched_ids = Vec::new();
+                    for item in items {
+                        touched_ids.push(item.id);
+                        self.trash.push(TodoItem::deserialize(item)?);
+                    }
+
+                    Ok(touched_ids)
+                }
+                StoreAction::CreateProject { project } => {
+                    self.projects.push(project);
+
+                    Ok(Vec::new())
+                }
+                StoreAction::RemoveProject { name } => {
+                    self.projects.retain(|project| project.name != name);
+
+                    Ok(Vec::new())
+                }
+                StoreAction::SaveView { view } => {
+                    match self.views.iter().position(|existing| existing.is_named(&view.name)) {
+                        Some(index) => self.views[index] = view,
+                        None => self.views.push(view),
+                    }
+
+                    Ok(Vec::new())
+                }
+                StoreAction::RemoveView { name } => {
+                    self.views.retain(|view| !view.is_named(&name));
+
+                    Ok(Vec::new())
+                }
+                StoreAction::RenameProject { name, new_name } => {
+                    self.get_project_mut(&name)?.name = new_name.clone();
+
+                    let mut touched_ids = Vec::new();
+                    for item in self.store.iter_mut().chain(self.trash.iter_mut()) {
+                        if item.project.as_ref() == Some(&name) {
+                            item.project = Some(new_name.clone());
+                            touched_ids.push(item.id);
+                        }
+                    }
+
+                    Ok(touched_ids)
+                }
+                StoreAction::ArchiveProject { name } => {
+                    self.get_project_mut(&name)?.archived = true;
+
+                    Ok(Vec::new())
+                }
+                StoreAction::UnarchiveProject { name } => {
+                    self.get_project_mut(&name)?.archived = false;
+
+                    Ok(Vec::new())
+                }
+                StoreAction::Batch { actions } => {
+                    let mut touched_ids = Vec::new();
+                    for action in actions {
+                        touched_ids.extend(self.apply(action)?);
+                    }
+
+                    Ok(touched_ids)
+                }
+                StoreAction::Undo | StoreAction::Redo => Err(TodoError::new_from_msg(
+                    String::from("Undo and redo must go through the undo history."),
+                )),
+            }
+        }
+
+        fn snapshot(&mut self) -> Result<(), TodoError> {
+            let snapshot = StoreSnapshot {
+                items: self.store.iter().map(TodoItemSerializable::from).collect(),
+                trash: self.trash.iter().map(TodoItemSerializable::from).collect(),
+                projects: self.projects.clone(),
+                views: self.views.clone(),
+                next_id: self.next_id,
+                history: self.history.clone(),
+            };
+
+            self.backend.snapshot(&snapshot)
+        }
+
+        fn get_item_mut(&mut self, id: usize) -> Result<&mut TodoItem, TodoError> {
+            self.store
+                .iter_mut()
+                .find(|item| item.id == id)
+                .ok_or(TodoError::new_from_msg(String::from(
+                    "Please select a valid ID.",
+                )))
+        }
+
+        // Complete an item and the given subtasks, then any ancestors left with only completed
+        // subtasks, all as a single undoable change
+        fn commit_completion(&mut self, id: usize, subtask_ids: Vec<usize>) -> Result<(), TodoError> {
+            let mut completed_ids: HashSet<usize> = subtask_ids.iter().copied().collect();
+            completed_ids.insert(id);
+
+            let at = Some(Utc::now());
+            let mut actions: Vec<StoreAction> = subtask_ids
+                .iter()
+                .map(|id| StoreAction::Complete { id: *id, at })
+                .collect();
+            actions.push(StoreAction::Complete { id, at });
+            let mut recurring_ids = subtask_ids;
+            recurring_ids.push(id);
+
+            for ancestor in self.ancestors(id) {
+                let all_subtasks_complete = self
+                    .list_subtasks(ancestor.id)
+                    .iter()
+                    .all(|item| item.complete || completed_ids.contains(&item.id));
+                if ancestor.complete || !all_subtasks_complete {
+                    break;
+                }
+                completed_ids.insert(ancestor.id);
+                actions.push(StoreAction::Complete { id: ancestor.id, at });
+                recurring_ids.push(ancestor.id);
+            }
+
+            // Completing a recurring item schedules its next occurrence, unless an earlier
+            // completion that was reopened since already did
+            let today = due_date::today();
+            let mut next_id = self.next_id;
+            for id in recurring_ids {
+                if self.store.iter().any(|item| item.recurs_from == Some(id)) {
+                    continue;
+                }
+                if let Some(mut item) = self.get_item(id)?.next_occurrence(next_id, today) {
+                    // Open items cannot sit below a completed parent
+                    let parent_complete = item.parent.is_some_and(|parent_id| {
+                        completed_ids.contains(&parent_id)
+                            || self.get_item(parent_id).map_or(true, |parent| parent.complete)
+                    });
+                    if parent_complete {
+                        item.parent = None;
+                    }
+                    actions.push(StoreAction::Create {
+                        item: TodoItemSerializable::from(&item),
+                    });
+                    next_id += 1;
+                }
+            }
+
+            self.commit(batch(actions))
+        }
+
+        // Open subtasks at any depth below an item, deepest first
+        fn open_subtasks(&self, id: usize) -> Result<Vec<usize>, TodoError> {
+            self.get_item(id)?;
+
+            let mut open_subtasks = Vec::new();
+            let mut visited = HashSet::from([id]);
+            self.collect_open_subtasks(id, &mut visited, &mut open_subtasks);
+
+            Ok(open_subtasks)
+        }
+
+        fn collect_open_subtasks(
+            &self,
+            id: usize,
+            visited: &mut HashSet<usize>,
+            open_subtasks: &mut Vec<usize>,
+        ) {
+            for subtask in self.list_subtasks(id) {
+                // Guards against a cycle in a hand edited file
+                if !visited.insert(subtask.id) {
+                    continue;
+                }
+                self.collect_open_subtasks(subtask.id, visited, open_subtasks);
+                if !subtask.complete {
+                    open_subtasks.push(subtask.id);
+                }
+            }
+        }
+
+        // Parent, grandparent and so on of an item, nearest first
+        fn ancestors(&self, id: usize) -> Vec<&TodoItem> {
+            let mut ancestors: Vec<&TodoItem> = Vec::new();
+            let mut parent_id = self.get_item(id).ok().and_then(|item| item.parent);
+            while let Some(id) = parent_id {
+                match self.get_item(id) {
+                    Ok(parent) if !ancestors.iter().any(|item| item.id == id) => {
+                        ancestors.push(parent);
+                        parent_id = parent.parent;
+                    }
+                    _ => break,
+                }
+            }
+
+            ancestors
+        }
+
+        // Check that the given item (or a new one when None) can become a subtask of parent_id
+        fn check_parent(&self, id: Option<usize>, parent_id: usize) -> Result<(), TodoError> {
+            let parent = self.get_item(parent_id).map_err(|_| {
+                TodoError::new_from_msg(format!("There is no item {} to add a subtask to.", parent_id))
+            })?;
+
+            if let Some(id) = id {
+                let is_cycle = parent_id == id
+                    || self.ancestors(parent_id).iter().any(|item| item.id == id);
+                if is_cycle {
+                    return Err(TodoError::new_from_msg(String::from(
+                        "An item cannot be a subtask of itself or of its own subtasks.",
+                    )));
+                }
+            }
+
+            let is_open = id.map_or(Ok(true), |id| self.get_item(id).map(|item| !item.complete))?;
+            if parent.complete && is_open {
+                return Err(TodoError::new_from_msg(format!(
+                    "Item {} is complete. Reopen it before adding open subtasks.",
+                    parent_id
+                )));
+            }
+
+            Ok(())
+        }
+
+        // Check that the given item (or a new one when None) can be blocked by every given item
+        // without creating a cycle
+        fn check_blockers(&self, id: Option<usize>, blocked_by: &BTreeSet<usize>) -> Result<(), TodoError> {
+            for blocker_id in blocked_by {
+                self.get_item(*blocker_id).map_err(|_| {
+                    TodoError::new_from_msg(format!("There is no item {} to be blocked by.", blocker_id))
+                })?;
+
+                if let Some(id) = id {
+                    if *blocker_id == id {
+                        return Err(TodoError::new_from_msg(String::from(
+                            "An item cannot block itself.",
+                        )));
+                    }
+                    if self.depends_on(*blocker_id, id) {
+                        return Err(TodoError::new_from_msg(format!(
+                            "Item {} cannot block item {} because it already depends on it.",
+                            blocker_id, id
+                        )));
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        // Whether the item with the given id is blocked, directly or through other blockers, by
+        // the item with dependency_id
+        fn depends_on(&self, id: usize, dependency_id: usize) -> bool {
+            let mut visited = HashSet::new();
+            let mut pending = vec![id];
+            while let Some(id) = pending.pop() {
+                if !visited.insert(id) {
+                    continue;
+                }
+                if let Ok(item) = self.get_item(id) {
+                    if item.blocked_by.contains(&dependency_id) {
+                        return true;
+                    }
+                    pending.extend(item.blocked_by.iter().copied());
                 }
+            }
+
+            false
+        }
+
+        fn get_project_mut(&mut self, name: &str) -> Result<&mut Project, TodoError> {
+            self.projects
+                .iter_mut()
+                .find(|project| project.is_named(name))
+                .ok_or(TodoError::new_from_msg(format!(
+                    "There is no project named '{}'.",
+                    name.trim()
+                )))
+        }
+
+        // Look up the exact name of a project that items can be added to
+        fn resolve_open_project(&self, name: &str) -> Result<String, TodoError> {
+            let project = self.get_project(name)?;
+            if project.archived {
+                return Err(TodoError::new_from_msg(format!(
+                    "Project '{}' is archived.",
+                    project.name
+                )));
+            }
+
+            Ok(project.name.clone())
+        }
+
+        fn in_archived_project(&self, item: &TodoItem) -> bool {
+            item.project.as_ref().is_some_and(|name| {
+                self.projects
+                    .iter()
+                    .any(|project| project.archived && &project.name == name)
             })
+        }
+
+        fn get_trashed_item(&self, id: usize) -> Result<&TodoItem, TodoError> {
+            self.trash
+                .iter()
+                .find(|item| item.id == id)
+                .ok_or(TodoError::new_from_msg(String::from(
+                    "Please select the ID of an item in the trash.",
+                )))
+        }
+
+        fn sort_store(&mut self) {
+            // Undated items come before every deadline when placed first, after them when last
+            let undated_first = self.undated_position == UndatedPosition::First;
+            let due_key = |item: &TodoItem| {
+                let deadline = item.deadline();
+                (deadline.is_some() == undated_first, deadline)
+            };
+
+            match self.sort_order {
+                SortOrder::DueDate => self
+                    .store
+                    .sort_by_cached_key(|item| (due_key(item), item.priority_rank())),
+                SortOrder::Priority => self
+                    .store
+                    .sort_by_cached_key(|item| (item.priority_rank(), due_key(item))),
+            }
+        }
+
+        fn get_filtered_store<F>(&self, filter: F) -> Vec<&TodoItem>
+        where
+            F: FnMut(&&TodoItem) -> bool, // TODO: Is a double reference necessary?
+        {
+            self.store
+                .iter()
+                .filter(|item| !self.in_archived_project(item))
+                .filter(filter)
+                .collect()
+        }
+    }
+
+    fn join_ids(ids: &[usize]) -> String {
+        ids.iter()
+            .map(usize::to_string)
             .collect::<Vec<String>>()
-            .join("|");
+            .join(", ")
+    }
+
+    // Wrap several actions so they are applied and undone together
+    fn batch(mut actions: Vec<StoreAction>) -> StoreAction {
+        match actions.len() {
+            1 => actions.remove(0),
+            _ => StoreAction::Batch { actions },
+        }
+    }
+}
+
+pub mod todo_printer {
+    use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+    use std::collections::{HashMap, HashSet};
+    use std::env;
+    use std::io::{self, IsTerminal};
+    use unicode_width::UnicodeWidthStr;
+
+    use super::todo_store::TodoStore;
+    use crate::err::TodoError;
+    use crate::todo::due_date;
+    use crate::todo::fuzzy::highlight;
+    use crate::todo::{Column, FuzzyMatch, Query, SavedView, Timestamp};
+    use crate::TodoItem;
+
+    #[derive(Debug, Clone)]
+    enum Justification {
+        Left,
+        Right,
+    }
+
+    type DataSupplier<'a> = dyn Fn(&TodoItem) -> String + 'a;
+    // Decorates a cell after it has been measured, e.g. with terminal colors
+    type CellStyler<'a> = dyn Fn(&TodoItem, String) -> String + 'a;
+
+    struct TableColumn<'a> {
+        pub header: &'static str,
+        pub justification: Justification,
+        pub data_supplier: Box<DataSupplier<'a>>,
+        pub styler: Option<Box<CellStyler<'a>>>,
+        pub width: usize,
+    }
+
+    impl<'a> TableColumn<'a> {
+        fn new(
+            header: &'static str,
+            justification: Justification,
+            data_supplier: Box<DataSupplier<'a>>,
+        ) -> TableColumn<'a> {
+            TableColumn {
+                header,
+                justification,
+                data_supplier,
+                styler: None,
+                width: header.width(),
+            }
+        }
+    }
+
+    // Shown when no columns are asked for
+    const DEFAULT_COLUMNS: [Column; 9] = [
+        Column::Id,
+        Column::Done,
+        Column::Due,
+        Column::Priority,
+        Column::Title,
+        Column::Repeats,
+        Column::BlockedBy,
+        Column::Tags,
+        Column::Project,
+    ];
+
+    // The regular columns followed by the timestamps the store is set to show
+    fn get_columns<'a>(
+        store: &'a TodoStore,
+        depths: &'a HashMap<usize, usize>,
+    ) -> Vec<TableColumn<'a>> {
+        DEFAULT_COLUMNS
+            .iter()
+            .copied()
+            .chain(store.timestamp_columns().iter().map(|timestamp| match timestamp {
+                Timestamp::Created => Column::Created,
+                Timestamp::Completed => Column::Completed,
+                Timestamp::Updated => Column::Updated,
+            }))
+            .map(|column| table_column(column, store, depths))
+            .collect()
+    }
+
+    // depths maps item ids to how far their titles are indented in the tree view
+    fn table_column<'a>(
+        column: Column,
+        store: &'a TodoStore,
+        depths: &'a HashMap<usize, usize>,
+    ) -> TableColumn<'a> {
+        match column {
+            Column::Id => TableColumn::new(
+                "#",
+                Justification::Right,
+                Box::new(|item| item.id.to_string()),
+            ),
+            Column::Done => TableColumn::new(
+                "√",
+                Justification::Left,
+                Box::new(|item| match item.complete {
+                    true => String::from("X"),
+                    false => String::from(" "),
+                }),
+            ),
+            Column::Due => TableColumn::new(
+                "Date due",
+                Justification::Left,
+                Box::new(|item| item.due_display()),
+            ),
+            Column::Priority => TableColumn::new(
+                "Priority",
+                Justification::Left,
+                Box::new(|item| {
+                    item.priority
+                        .map_or(String::new(), |priority| priority.to_string())
+                }),
+            ),
+            Column::Title => TableColumn::new(
+                "Title",
+                Justification::Left,
+                Box::new(move |item| {
+                    let indent = "  ".repeat(depths.get(&item.id).copied().unwrap_or(0));
+                    match store.subtask_progress(item.id) {
+                        Some((completed_count, total_count)) => format!(
+                            "{}{} ({}/{})",
+                            indent, item.title, completed_count, total_count
+                        ),
+                        None => format!("{}{}", indent, item.title),
+                    }
+                }),
+            ),
+            Column::Repeats => TableColumn::new(
+                "Repeats",
+                Justification::Left,
+                Box::new(|item| {
+                    item.recurrence
+                        .as_ref()
+                        .map_or(String::new(), |recurrence| recurrence.to_string())
+                }),
+            ),
+            Column::BlockedBy => TableColumn::new(
+                "Blocked by",
+                Justification::Left,
+                Box::new(move |item| {
+                    store
+                        .open_blockers(item)
+                        .iter()
+                        .map(usize::to_string)
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                }),
+            ),
+            Column::Tags => {
+                TableColumn::new("Tags", Justification::Left, Box::new(|item| item.tags_display()))
+            }
+            Column::Project => TableColumn::new(
+                "Project",
+                Justification::Left,
+                Box::new(|item| item.project.clone().unwrap_or_default()),
+            ),
+            Column::Created => timestamp_column(Timestamp::Created),
+            Column::Completed => timestamp_column(Timestamp::Completed),
+            Column::Updated => timestamp_column(Timestamp::Updated),
+        }
+    }
+
+    fn timestamp_column<'a>(timestamp: Timestamp) -> TableColumn<'a> {
+        TableColumn::new(
+            timestamp.header(),
+            Justification::Left,
+            Box::new(move |item| item.timestamp_display(timestamp)),
+        )
+    }
+
+    pub fn print_store(store: &TodoStore, data_title: &str, collection: &[&TodoItem]) {
+        let depths = HashMap::new();
+
+        print_table(data_title, collection, get_columns(store, &depths));
+    }
+
+    // Completed items, most recent first, always with their completion time
+    pub fn print_history(store: &TodoStore) {
+        let depths = HashMap::new();
+        let mut columns = get_columns(store, &depths);
+        if !store.timestamp_columns().contains(&Timestamp::Completed) {
+            columns.push(timestamp_column(Timestamp::Completed));
+        }
+
+        print_table("Completed items", &store.list_history(), columns);
+    }
+
+    // Items matching a saved view, with the columns it was saved with
+    pub fn print_view(store: &TodoStore, view: &SavedView) -> Result<(), TodoError> {
+        let query = Query::parse(&view.query)?;
+        let depths = HashMap::new();
+        let columns = match view.columns.is_empty() {
+            true => get_columns(store, &depths),
+            false => view
+                .columns
+                .iter()
+                .map(|column| table_column(*column, store, &depths))
+                .collect(),
+        };
+
+        print_table(&format!("View: {}", view.name), &store.search(&query), columns);
+
+        Ok(())
+    }
+
+    // Fuzzy search results in the order given. Matched title characters are highlighted when
+    // printing to a terminal that allows colors.
+    pub fn print_matches(store: &TodoStore, data_title: &str, matches: &[(&TodoItem, FuzzyMatch)]) {
+        let depths = HashMap::new();
+        let positions: HashMap<usize, &[usize]> = matches
+            .iter()
+            .map(|(item, found)| (item.id, found.positions.as_slice()))
+            .collect();
+        let mut columns = get_columns(store, &depths);
+        if use_highlighting() {
+            if let Some(title_column) = columns.iter_mut().find(|column| column.header == "Title") {
+                title_column.styler = Some(Box::new(|item, data| {
+                    highlight(&data, positions.get(&item.id).copied().unwrap_or_default())
+                }));
+            }
+        }
+        let items: Vec<&TodoItem> = matches.iter().map(|(item, _)| *item).collect();
+
+        print_table(data_title, &items, columns);
+    }
+
+    fn use_highlighting() -> bool {
+        io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none()
+    }
+
+    // Sections of the agenda, in the order they are printed
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum AgendaSection {
+        Overdue,
+        Today,
+        Tomorrow,
+        ThisWeek,
+        Later,
+        NoDate,
+    }
+
+    impl AgendaSection {
+        const ALL: [AgendaSection; 6] = [
+            AgendaSection::Overdue,
+            AgendaSection::Today,
+            AgendaSection::Tomorrow,
+            AgendaSection::ThisWeek,
+            AgendaSection::Later,
+            AgendaSection::NoDate,
+        ];
+
+        // Items are overdue once their deadline has passed, the way list --overdue sees them, so
+        // an item due at 9am today is overdue by 10am. Weeks end on Sunday, so on a Saturday
+        // nothing but tomorrow is left of this week.
+        fn of(item: &TodoItem, today: NaiveDate, now: DateTime<Utc>) -> AgendaSection {
+            let end_of_week =
+                today + Duration::days(6 - today.weekday().num_days_from_monday() as i64);
+
+            match item.due_date {
+                _ if item.is_overdue(now) => AgendaSection::Overdue,
+                None => AgendaSection::NoDate,
+                Some(date) if date <= today => AgendaSection::Today,
+                Some(date) if date == today + Duration::days(1) => AgendaSection::Tomorrow,
+                Some(date) if date <= end_of_week => AgendaSection::ThisWeek,
+                Some(_) => AgendaSection::Later,
+            }
+        }
+
+        fn title(&self) -> &'static str {
+            match self {
+                AgendaSection::Overdue => "Overdue",
+                AgendaSection::Today => "Today",
+                AgendaSection::Tomorrow => "Tomorrow",
+                AgendaSection::ThisWeek => "This week",
+                AgendaSection::Later => "Later",
+                AgendaSection::NoDate => "No date",
+            }
+        }
+    }
+
+    // Incomplete items grouped by when they are due. Empty sections are left out and items keep
+    // the store order within their section.
+    fn agenda_sections<'a>(
+        collection: &[&'a TodoItem],
+        today: NaiveDate,
+        now: DateTime<Utc>,
+    ) -> Vec<(AgendaSection, Vec<&'a TodoItem>)> {
+        AgendaSection::ALL
+            .iter()
+            .filter_map(|section| {
+                let items: Vec<&TodoItem> = collection
+                    .iter()
+                    .copied()
+                    .filter(|item| {
+                        !item.complete && AgendaSection::of(item, today, now) == *section
+                    })
+                    .collect();
+                (!items.is_empty()).then_some((*section, items))
+            })
+            .collect()
+    }
+
+    pub fn print_agenda(store: &TodoStore) {
+        print_agenda_relative_to(store, due_date::today(), Utc::now());
+    }
+
+    // Every section is a table of its own, all with the same column widths so they line up
+    pub fn print_agenda_relative_to(store: &TodoStore, today: NaiveDate, now: DateTime<Utc>) {
+        let depths = HashMap::new();
+        let items = store.list_incomplete_todos();
+        let sections = agenda_sections(&items, today, now);
+        if sections.is_empty() {
+            println!("Nothing left to do.");
+            return;
+        }
+
+        let mut columns = get_columns(store, &depths);
+        fit_columns(&items, &mut columns);
+        for (i, (section, items)) in sections.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            print_title(section.title(), &columns);
+            print_header_rows(&columns);
+            items.iter().for_each(|item| print_table_row(&columns, item));
+        }
+    }
+
+    // Print subtasks indented below their parents. Items whose parent is not part of the collection
+    // are shown at the top level.
+    pub fn print_tree(store: &TodoStore, data_title: &str, collection: &[&TodoItem]) {
+        let ids: HashSet<usize> = collection.iter().map(|item| item.id).collect();
+        let mut rows = Vec::new();
+        let mut depths = HashMap::new();
+
+        for item in collection {
+            if item.parent.is_none_or(|parent_id| !ids.contains(&parent_id)) {
+                add_tree_rows(item, 0, collection, &mut rows, &mut depths);
+            }
+        }
+
+        print_table(data_title, &rows, get_columns(store, &depths));
+    }
+
+    fn add_tree_rows<'a>(
+        item: &'a TodoItem,
+        depth: usize,
+        collection: &[&'a TodoItem],
+        rows: &mut Vec<&'a TodoItem>,
+        depths: &mut HashMap<usize, usize>,
+    ) {
+        if depths.insert(item.id, depth).is_some() {
+            return;
+        }
+        rows.push(item);
+
+        for subtask in collection.iter().filter(|subtask| subtask.parent == Some(item.id)) {
+            add_tree_rows(subtask, depth + 1, collection, rows, depths);
+        }
+    }
+
+    fn print_table(data_title: &str, collection: &[&TodoItem], mut table_columns: Vec<TableColumn>) {
+        fit_columns(collection, &mut table_columns);
+
+        // Print all data
+        print_title(data_title, &table_columns);
+        print_header_rows(&table_columns);
+        collection
+            .iter()
+            .for_each(|item| print_table_row(&table_columns, item));
+    }
+
+    // Widen every column to its widest cell. Widths are measured in terminal columns so wide
+    // characters such as CJK and emoji line up.
+    fn fit_columns(collection: &[&TodoItem], table_columns: &mut [TableColumn]) {
+        collection.iter().for_each(|item| {
+            for column in table_columns.iter_mut() {
+                let item_data_at_column = (column.data_supplier)(item);
+                if item_data_at_column.width() > column.width {
+                    column.width = item_data_at_column.width();
+                }
+            }
+        });
+    }
+
+    pub fn print_projects(store: &TodoStore) {
+        println!("=== Projects ===");
+        if store.list_projects().is_empty() {
+            println!(" No projects yet.");
+        }
+        for project in store.list_projects() {
+            let item_count = store.list_project(&project.name).map_or(0, |items| items.len());
+            println!(" {} - {} item(s)", project, item_count);
+        }
+    }
+
+    pub fn print_views(store: &TodoStore) {
+        println!("=== Saved views ===");
+        if store.list_views().is_empty() {
+            println!(" No saved views yet.");
+        }
+        for view in store.list_views() {
+            println!(" {}", view);
+        }
+    }
+
+    fn print_title(data_title: &str, columns: &[TableColumn]) {
+        // Calculate the total width of the table
+        let table_column_width_sum: usize = columns.iter().map(|column| column.width).sum();
+        let total_table_width = table_column_width_sum +
+            (columns.len() * 2) + // Padding chars
+            (columns.len() - 1); // Dividers
+
+        // Calculate padding char count for left and right padding
+        let total_padding_char_count = total_table_width.saturating_sub(data_title.width() + 2);
+        let left_padding_length = total_padding_char_count / 2;
+        // If total_padding_char_count is odd, add an extra char to the right padding
+        let right_padding_length = left_padding_length + (total_padding_char_count % 2);
+
+        // Create left and right padding strings
+        let left_padding = String::from("=").repeat(left_padding_length);
+        let right_padding = String::from("=").repeat(right_padding_length);
+
+        // Print title
+        println!("{} {} {}", left_padding, data_title, right_padding);
+    }
+
+    fn print_header_rows(columns: &[TableColumn]) {
+        // Initialize header and divider cells
+        let mut header_cells: Vec<String> = Vec::new();
+        let mut divider_cells: Vec<String> = Vec::new();
+
+        // For every TableColumn, create a left-justified string representing each header cell and
+        // a divider with the length of the column
+        columns.iter().for_each(|column| {
+            let header_right_padding_char_count = column.width - column.header.width();
+            header_cells.push(format!(
+                " {}{} ",
+                column.header,
+                String::from(" ").repeat(header_right_padding_char_count)
+            ));
+            divider_cells.push(format!("-{}-", String::from("-").repeat(column.width)));
+        });
+
+        // Print out all cells separated by a pipe
+        println!("{}", header_cells.join("|"));
+        println!("{}", divider_cells.join("|"));
+    }
+
+    fn print_table_row(columns: &[TableColumn], item: &TodoItem) {
+        println!("{}", format_table_row(columns, item));
+    }
+
+    fn format_table_row(columns: &[TableColumn], item: &TodoItem) -> String {
+        // Create a string representing the TodoItem data for each column
+        columns
+            .iter()
+            .map(|column| {
+                let data: String = (column.data_supplier)(item);
+                let padding_char_count = column.width - data.width();
+                let data = match &column.styler {
+                    Some(styler) => styler(item, data),
+                    None => data,
+                };
+
+                match column.justification {
+                    Justification::Left => {
+                        format!(" {}{} ", data, String::from(" ").repeat(padding_char_count))
+                    },
+                    Justification::Right => {
+                        format!(" {}{} ", String::from(" ").repeat(padding_char_count), data)
+                    },
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("|")
+    }
+
+    #[cfg(test)]
+    mod todo_printer_tests {
+        use super::*;
+        use chrono::TimeZone;
+
+        #[test]
+        fn wide_characters_line_up() {
+            let due_date = Some(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap());
+            let items: Vec<TodoItem> = ["Tea", "お茶を買う", "Café ☕"]
+                .iter()
+                .enumerate()
+                .map(|(id, title)| TodoItem::from_parts(id, due_date, String::from(*title)).unwrap())
+                .collect();
+            let mut columns = vec![
+                TableColumn::new(
+                    "Title",
+                    Justification::Left,
+                    Box::new(|item| item.title.clone()),
+                ),
+                TableColumn::new("#", Justification::Right, Box::new(|item| item.id.to_string())),
+            ];
+
+            fit_columns(&items.iter().collect::<Vec<&TodoItem>>(), &mut columns);
+
+            assert_eq!(10, columns[0].width);
+            for item in &items {
+                let row = format_table_row(&columns, item);
+                assert_eq!(16, row.width(), "{}", row);
+                assert!(row.ends_with(&format!("| {} ", item.id)));
+            }
+        }
+
+        #[test]
+        fn agenda_groups_by_due_date() {
+            // A Wednesday
+            let today = NaiveDate::from_ymd_opt(2023, 1, 11).unwrap();
+            let now = Utc.with_ymd_and_hms(2023, 1, 11, 0, 0, 0).unwrap();
+            let mut items: Vec<TodoItem> = [
+                Some(NaiveDate::from_ymd_opt(2023, 1, 20).unwrap()),
+                Some(NaiveDate::from_ymd_opt(2023, 1, 12).unwrap()),
+                None,
+                Some(NaiveDate::from_ymd_opt(2023, 1, 15).unwrap()),
+                Some(NaiveDate::from_ymd_opt(2023, 1, 11).unwrap()),
+                Some(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap()),
+                Some(NaiveDate::from_ymd_opt(2023, 1, 13).unwrap()),
+                Some(NaiveDate::from_ymd_opt(2023, 1, 16).unwrap()),
+            ]
+            .iter()
+            .enumerate()
+            .map(|(id, due_date)| {
+                TodoItem::from_parts(id, *due_date, format!("Item {}", id)).unwrap()
+            })
+            .collect();
+            items[6].mark_as_done(None);
+
+            let sections: Vec<(&str, Vec<usize>)> =
+                agenda_sections(&items.iter().collect::<Vec<&TodoItem>>(), today, now)
+                    .iter()
+                    .map(|(section, items)| {
+                        (section.title(), items.iter().map(|item| item.id).collect())
+                    })
+                    .collect();
+
+            assert_eq!(
+                vec![
+                    ("Overdue", vec![5]),
+                    ("Today", vec![4]),
+                    ("Tomorrow", vec![1]),
+                    ("This week", vec![3]),
+                    ("Later", vec![0, 7]),
+                    ("No date", vec![2]),
+                ],
+                sections
+            );
+        }
+
+        #[test]
+        fn agenda_week_ends_on_sunday() {
+            let saturday = NaiveDate::from_ymd_opt(2023, 1, 14).unwrap();
+            let now = Utc.with_ymd_and_hms(2023, 1, 14, 0, 0, 0).unwrap();
+            let section = |date| {
+                let item = TodoItem::from_parts(0, Some(date), String::from("Todo")).unwrap();
+                AgendaSection::of(&item, saturday, now)
+            };
+
+            assert_eq!(
+                AgendaSection::Tomorrow,
+                section(NaiveDate::from_ymd_opt(2023, 1, 15).unwrap())
+            );
+            assert_eq!(AgendaSection::Later, section(NaiveDate::from_ymd_opt(2023, 1, 16).unwrap()));
+        }
+
+        #[test]
+        fn agenda_overdue_by_deadline() {
+            let today = NaiveDate::from_ymd_opt(2023, 1, 11).unwrap();
+            let now = Utc.with_ymd_and_hms(2023, 1, 11, 10, 0, 0).unwrap();
+            let section = |input: &str| {
+                let item = TodoItem::new(String::from(input), 0).unwrap();
+                AgendaSection::of(&item, today, now)
+            };
+
+            assert_eq!(AgendaSection::Overdue, section("2023-01-11 9:00 UTC Standup"));
+            assert_eq!(AgendaSection::Today, section("2023-01-11 11:00 UTC Review"));
+            // 11:00 UTC on the 11th, so not overdue yet
+            assert_eq!(AgendaSection::Today, section("2023-01-10 23:00 -12:00 Late call"));
+            assert_eq!(AgendaSection::Overdue, section("2023-01-09 Plan"));
+        }
+
+        #[test]
+        fn styled_cells_keep_their_width() {
+            let item = TodoItem::from_parts(1, None, String::from("Deploy api")).unwrap();
+            let mut columns = vec![TableColumn::new(
+                "Title",
+                Justification::Left,
+                Box::new(|item| item.title.clone()),
+            )];
+            columns[0].styler = Some(Box::new(|_, data| highlight(&data, &[7, 8, 9])));
+
+            fit_columns(&[&item], &mut columns);
+
+            assert_eq!(" Deploy \x1b[1;4mapi\x1b[0m ", format_table_row(&columns, &item));
+        }
+    }
+}
+
+#[cfg(test)]
+mod todo_store_tests {
+    use super::todo_store::{SortOrder, TodoStore, UndatedPosition};
+    use crate::err::TodoError;
+    use crate::todo::{
+        Column, DueTime, ItemChanges, ItemInput, Priority, Query, SavedView, SqliteBackend,
+        TodoItem,
+    };
+    use crate::todo::action::UndoHistory;
+    use crate::todo::{LoadedStore, StorageBackend, StoreChange, StoreSnapshot};
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use std::cell::Cell;
+    use std::env;
+    use std::fs;
+    use std::rc::Rc;
+
+    fn test_filepath(name: &str) -> String {
+        let filepath = env::temp_dir().join(format!("todo_assistant_{}.json", name));
+        let filepath = String::from(filepath.to_str().unwrap());
+
+        fs::write(&filepath, r#"{"schema_version": 6, "store": [], "trash": [], "projects": [], "views": [], "history": {"undo": [], "redo": []}, "next_id": 0}"#).unwrap();
+        let _ = fs::remove_file(format!("{}.journal", filepath));
+
+        filepath
+    }
+
+    fn new_test_store(name: &str) -> TodoStore {
+        TodoStore::new_from_persistence(&test_filepath(name)).unwrap()
+    }
+
+    #[test]
+    fn add_greenpath() {
+        let mut store = new_test_store("add_greenpath");
+        let due_date = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+
+        let item = store.add(due_date, "First Todo").unwrap();
+
+        assert_eq!(0, item.id);
+        assert_eq!(Some(due_date), item.due_date);
+        assert_eq!("First Todo", item.title);
+        assert_eq!(1, store.list_incomplete_todos().len());
+    }
+
+    #[test]
+    fn add_sorts_by_due_date() {
+        let mut store = new_test_store("add_sorts_by_due_date");
+        store.add(NaiveDate::from_ymd_opt(2023, 2, 2).unwrap(), "Later").unwrap();
+        store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Sooner").unwrap();
+
+        let titles: Vec<&str> = store
+            .list_all_todos()
+            .iter()
+            .map(|item| item.title.as_str())
+            .collect();
+
+        assert_eq!(vec!["Sooner", "Later"], titles);
+    }
+
+    #[test]
+    fn priority_sorting() {
+        let filepath = test_filepath("priority_sorting");
+        {
+            let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+            store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "None").unwrap();
+            store
+                .create(ItemInput {
+                    priority: Some(Priority::Low),
+                    ..ItemInput::new(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Low")
+                })
+                .unwrap();
+            store
+                .create(ItemInput {
+                    priority: Some(Priority::High),
+                    ..ItemInput::new(NaiveDate::from_ymd_opt(2023, 2, 2).unwrap(), "High")
+                })
+                .unwrap();
+        }
+
+        let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+        let titles = |store: &TodoStore| -> Vec<String> {
+            store
+                .list_all_todos()
+                .iter()
+                .map(|item| item.title.clone())
+                .collect()
+        };
+
+        // Priority breaks ties between items due on the same day
+        assert_eq!(vec!["Low", "None", "High"], titles(&store));
+
+        store.sort_by(SortOrder::Priority);
+        assert_eq!(vec!["High", "Low", "None"], titles(&store));
+    }
+
+    #[test]
+    fn edit_priority_undone() {
+        let mut store = new_test_store("edit_priority_undone");
+        let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+
+        let changes = ItemChanges {
+            priority: Some(Some(Priority::Medium)),
+            ..ItemChanges::default()
+        };
+        assert_eq!(Some(Priority::Medium), store.edit(id, changes).unwrap().priority);
+
+        store.undo().unwrap();
+        assert_eq!(None, store.get_item(id).unwrap().priority);
+    }
+
+    #[test]
+    fn tags_persisted_and_filtered() {
+        let filepath = test_filepath("tags_persisted_and_filtered");
+        {
+            let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+            store
+                .create(TodoItem::parse_input("2023-01-01 Rotate keys #ops").unwrap())
+                .unwrap();
+            store
+                .create(TodoItem::parse_input("2023-01-02 Fix login #backend #ops").unwrap())
+                .unwrap();
+            store.add(NaiveDate::from_ymd_opt(2023, 1, 3).unwrap(), "Untagged").unwrap();
+            store.compact().unwrap();
+        }
+
+        let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+        assert_eq!(2, store.list_tagged("ops").len());
+        assert_eq!(1, store.list_tagged("#Backend").len());
+
+        let changes = ItemChanges {
+            tags: Some(TodoItem::parse_tags("none").unwrap()),
+            ..ItemChanges::default()
+        };
+        store.edit(1, changes).unwrap();
+        assert!(store.list_tagged("backend").is_empty());
+    }
+
+    #[test]
+    fn projects_lifecycle() {
+        let filepath = test_filepath("projects_lifecycle");
+        {
+            let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+            store.create_project("Work").unwrap();
+            store.create_project("Home").unwrap();
+            store
+                .create(TodoItem::parse_input("2023-01-01 Report @work").unwrap())
+                .unwrap();
+            let id = store
+                .add(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(), "Groceries")
+                .unwrap()
+                .id;
+            store.move_item(id, Some("home")).unwrap();
+
+            // Renaming carries the items along
+            store.rename_project("Work", "Office").unwrap();
+        }
+
+        let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+        assert_eq!(Some(String::from("Office")), store.get_item(0).unwrap().project);
+        assert_eq!(1, store.list_project("office").unwrap().len());
+        assert!(store.create_project("HOME").is_err());
+
+        // Archived projects drop out of the regular lists but keep their own view
+        store.archive_project("Office").unwrap();
+        assert_eq!(1, store.list_all_todos().len());
+        assert_eq!(1, store.list_project("Office").unwrap().len());
+        assert_eq!(
+            "Project 'Office' is archived.",
+            &store.move_item(1, Some("Office")).err().unwrap().message
+        );
+
+        store.undo().unwrap();
+        store.undo().unwrap();
+        assert_eq!(Some(String::from("Work")), store.get_item(0).unwrap().project);
+    }
+
+    #[test]
+    fn create_in_unknown_project() {
+        let mut store = new_test_store("create_in_unknown_project");
+
+        let error = store
+            .create(TodoItem::parse_input("2023-01-01 Report @nowhere").unwrap())
+            .err()
+            .unwrap();
+
+        assert_eq!("There is no project named 'nowhere'.", &error.message);
+    }
+
+    #[test]
+    fn only_existing_projects_are_markers() {
+        let mut store = new_test_store("only_existing_projects_are_markers");
+        store.create_project("work").unwrap();
+
+        let input = store.parse_input("Email @bob @Work").unwrap();
+        let item = store.create(input).unwrap();
+
+        assert_eq!("Email @bob", item.title);
+        assert_eq!(Some(String::from("work")), item.project);
+    }
+
+    fn add_subtask(store: &mut TodoStore, parent_id: usize, title: &str) -> usize {
+        let input = ItemInput {
+            parent: Some(parent_id),
+            ..ItemInput::new(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), title)
+        };
+
+        store.create(input).unwrap().id
+    }
+
+    #[test]
+    fn parent_with_open_subtasks_needs_force() {
+        let mut store = new_test_store("parent_with_open_subtasks_needs_force");
+        let parent_id = store
+            .add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Launch")
+            .unwrap()
+            .id;
+        let first_id = add_subtask(&mut store, parent_id, "Write docs");
+        let second_id = add_subtask(&mut store, first_id, "Proofread");
+
+        let error = store.complete(parent_id).err().unwrap();
+        assert_eq!(
+            "Item 0 has 2 open subtask(s). Complete them first or force completion.",
+            &error.message
+        );
+
+        store.force_complete(parent_id).unwrap();
+        assert_eq!(3, store.list_history().len());
+
+        // Forcing is a single change
+        store.undo().unwrap();
+        assert!(!store.get_item(second_id).unwrap().complete);
+        assert!(store.list_history().is_empty());
+    }
+
+    #[test]
+    fn last_subtask_completes_parent() {
+        let filepath = test_filepath("last_subtask_completes_parent");
+        {
+            let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+            let parent_id = store
+                .add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Launch")
+                .unwrap()
+                .id;
+            let first_id = add_subtask(&mut store, parent_id, "Write docs");
+            let second_id = add_subtask(&mut store, parent_id, "Ship");
+
+            store.complete(first_id).unwrap();
+            assert_eq!(Some((1, 2)), store.subtask_progress(parent_id));
+            assert!(!store.get_item(parent_id).unwrap().complete);
+
+            store.complete(second_id).unwrap();
+        }
+
+        // The batch is journaled and replayed like any other action
+        let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+        assert!(store.get_item(0).unwrap().complete);
+        assert_eq!(Some((2, 2)), store.subtask_progress(0));
+
+        // Reopening a subtask reopens its parent
+        store.reopen(1).unwrap();
+        assert!(!store.get_item(0).unwrap().complete);
+    }
+
+    #[test]
+    fn subtask_cycles_rejected() {
+        let mut store = new_test_store("subtask_cycles_rejected");
+        let parent_id = store
+            .add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Launch")
+            .unwrap()
+            .id;
+        let child_id = add_subtask(&mut store, parent_id, "Write docs");
+
+        let changes = ItemChanges {
+            parent: Some(Some(child_id)),
+            ..ItemChanges::default()
+        };
+        let error = store.edit(parent_id, changes).err().unwrap();
+
+        assert_eq!(
+            "An item cannot be a subtask of itself or of its own subtasks.",
+            &error.message
+        );
+        assert!(store
+            .create(ItemInput {
+                parent: Some(42),
+                ..ItemInput::new(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Orphan")
+            })
+            .is_err());
+    }
+
+    fn block(store: &mut TodoStore, id: usize, blockers: &[usize]) -> Result<(), TodoError> {
+        let changes = ItemChanges {
+            blocked_by: Some(blockers.iter().copied().collect()),
+            ..ItemChanges::default()
+        };
+
+        store.edit(id, changes).map(|_| ())
+    }
+
+    #[test]
+    fn blocked_item_needs_force() {
+        let mut store = new_test_store("blocked_item_needs_force");
+        let first_id = store
+            .add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Design")
+            .unwrap()
+            .id;
+        let second_id = store
+            .add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Review")
+            .unwrap()
+            .id;
+        let blocked_id = store
+            .add(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(), "Build")
+            .unwrap()
+            .id;
+        block(&mut store, blocked_id, &[first_id, second_id]).unwrap();
+
+        assert!(store.is_blocked(store.get_item(blocked_id).unwrap()));
+        let ready_ids: Vec<usize> = store.list_ready().iter().map(|item| item.id).collect();
+        assert_eq!(vec![first_id, second_id], ready_ids);
+
+        let error = store.complete(blocked_id).err().unwrap();
+        assert_eq!(
+            "Item 2 is blocked by open item(s) 0, 1. Complete them first or force completion.",
+            &error.message
+        );
+
+        // Completed blockers no longer count
+        store.complete(first_id).unwrap();
+        store.complete(second_id).unwrap();
+        assert!(!store.is_blocked(store.get_item(blocked_id).unwrap()));
+        assert_eq!(blocked_id, store.list_ready()[0].id);
+
+        store.reopen(second_id).unwrap();
+        store.force_complete(blocked_id).unwrap();
+        assert!(store.get_item(blocked_id).unwrap().complete);
+    }
+
+    #[test]
+    fn blocker_cycles_rejected() {
+        let mut store = new_test_store("blocker_cycles_rejected");
+        let first_id = store
+            .add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Design")
+            .unwrap()
+            .id;
+        let second_id = store
+            .add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Build")
+            .unwrap()
+            .id;
+        let third_id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Ship").unwrap().id;
+        block(&mut store, second_id, &[first_id]).unwrap();
+        block(&mut store, third_id, &[second_id]).unwrap();
+
+        let error = block(&mut store, first_id, &[third_id]).err().unwrap();
+        assert_eq!(
+            "Item 2 cannot block item 0 because it already depends on it.",
+            &error.message
+        );
+        assert!(block(&mut store, first_id, &[first_id]).is_err());
+        assert!(block(&mut store, first_id, &[42]).is_err());
+        assert!(store.get_item(first_id).unwrap().blocked_by.is_empty());
+    }
+
+    #[test]
+    fn recurring_item_spawns_next_occurrence() {
+        let filepath = test_filepath("recurring_item_spawns_next_occurrence");
+        {
+            let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+            let id = store
+                .create(TodoItem::parse_input("2023-01-06 Weekly report #ops *weekly:fri").unwrap())
+                .unwrap()
+                .id;
+            store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "One off").unwrap();
+
+            store.complete(id).unwrap();
+        }
+
+        let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+        let next = store.get_item(2).unwrap();
+        assert_eq!(Some(NaiveDate::from_ymd_opt(2023, 1, 13).unwrap()), next.due_date);
+        assert_eq!("Weekly report", next.title);
+        assert!(next.has_tag("ops"));
+        assert!(!next.complete);
+        assert_eq!(
+            Some(String::from("weekly:fri")),
+            next.recurrence.as_ref().map(|rule| rule.to_string())
+        );
+
+        // Completing and scheduling are undone together
+        store.undo().unwrap();
+        assert!(!store.get_item(0).unwrap().complete);
+        assert!(store.get_item(2).is_err());
+    }
+
+    #[test]
+    fn reopened_recurring_item_schedules_once() {
+        let mut store = new_test_store("reopened_recurring_item_schedules_once");
+        let id = store
+            .create(TodoItem::parse_input("2023-01-06 Report *weekly").unwrap())
+            .unwrap()
+            .id;
+
+        store.complete(id).unwrap();
+        store.reopen(id).unwrap();
+        store.complete(id).unwrap();
+
+        let titles: Vec<(usize, &str)> = store
+            .list_all_todos()
+            .iter()
+            .map(|item| (item.id, item.title.as_str()))
+            .collect();
+        assert_eq!(vec![(0, "Report"), (1, "Report")], titles);
+        assert_eq!(Some(id), store.get_item(1).unwrap().recurs_from);
+
+        // Completing the next occurrence schedules the one after it
+        store.complete(1).unwrap();
+        assert_eq!(
+            Some(NaiveDate::from_ymd_opt(2023, 1, 20).unwrap()),
+            store.get_item(2).unwrap().due_date
+        );
+    }
+
+    #[test]
+    fn due_times_sort_and_go_overdue() {
+        let mut store = new_test_store("due_times_sort_and_go_overdue");
+        store
+            .create(TodoItem::parse_input("2023-01-02 Next day").unwrap())
+            .unwrap();
+        store
+            .create(TodoItem::parse_input("2023-01-01 18:00 UTC Evening").unwrap())
+            .unwrap();
+        store
+            .create(TodoItem::parse_input("2023-01-01 09:00 UTC Morning").unwrap())
+            .unwrap();
+
+        let titles: Vec<&str> = store
+            .list_all_todos()
+            .iter()
+            .map(|item| item.title.as_str())
+            .collect();
+        assert_eq!(vec!["Morning", "Evening", "Next day"], titles);
+
+        let overdue = store.list_overdue(Utc.with_ymd_and_hms(2023, 1, 1, 12, 0, 0).unwrap());
+        assert_eq!(1, overdue.len());
+        assert_eq!("Morning", overdue[0].title);
+    }
+
+    #[test]
+    fn deadline_past_the_date_range_still_sorts() {
+        let mut store = new_test_store("deadline_past_the_date_range_still_sorts");
+        let mut input = ItemInput::new(NaiveDate::MAX, "Edge");
+        input.due_time = Some(DueTime::parse("23:00 -12:00").unwrap());
+        store.create(input).unwrap();
+        store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Next").unwrap();
+
+        assert_eq!("Next", store.list_all_todos()[0].title);
+        let overdue = store.list_overdue(Utc::now());
+        assert_eq!(vec!["Next"], overdue.iter().map(|item| item.title.as_str()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn undated_items_sort_first_or_last() {
+        let mut store = new_test_store("undated_items_sort_first_or_last");
+        store.create(TodoItem::parse_input("Someday").unwrap()).unwrap();
+        store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Dated").unwrap();
+        let titles = |store: &TodoStore| -> Vec<String> {
+            store
+                .list_all_todos()
+                .iter()
+                .map(|item| item.title.clone())
+                .collect()
+        };
+
+        assert_eq!(vec!["Dated", "Someday"], titles(&store));
+        store.place_undated(UndatedPosition::First);
+        assert_eq!(vec!["Someday", "Dated"], titles(&store));
+
+        // Removing the due date also removes the due time
+        let id = store
+            .create(TodoItem::parse_input("2023-01-02 9am Standup").unwrap())
+            .unwrap()
+            .id;
+        let changes = ItemChanges {
+            due_date: Some(None),
+            ..ItemChanges::default()
+        };
+        let item = store.edit(id, changes).unwrap();
+        assert_eq!(None, item.due_date);
+        assert_eq!(None, item.due_time);
+    }
+
+    #[test]
+    fn complete_greenpath() {
+        let mut store = new_test_store("complete_greenpath");
+        let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+
+        store.complete(id).unwrap();
+
+        assert!(store.get_item(id).unwrap().complete);
+        assert_eq!(1, store.list_history().len());
+    }
+
+    #[test]
+    fn reopen_greenpath() {
+        let filepath = test_filepath("reopen_greenpath");
+        {
+            let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+            let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+            store.complete(id).unwrap();
+            store.reopen(id).unwrap();
+        }
+
+        let store = TodoStore::new_from_persistence(&filepath).unwrap();
+
+        assert!(!store.get_item(0).unwrap().complete);
+        assert!(store.list_history().is_empty());
+    }
+
+    #[test]
+    fn reopen_incomplete_item() {
+        let mut store = new_test_store("reopen_incomplete_item");
+        let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+
+        let error = store.reopen(id).err().unwrap();
+
+        assert_eq!("Only completed items can be reopened.", &error.message);
+    }
+
+    #[test]
+    fn edit_greenpath() {
+        let mut store = new_test_store("edit_greenpath");
+        store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "First").unwrap();
+        let id = store.add(NaiveDate::from_ymd_opt(2023, 2, 2).unwrap(), "Secnod").unwrap().id;
+
+        let item = store
+            .edit(
+                id,
+                ItemChanges {
+                    title: Some(String::from("Second")),
+                    due_date: Some(Some(NaiveDate::from_ymd_opt(2022, 12, 31).unwrap())),
+                    ..ItemChanges::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!("Second", item.title);
+        assert_eq!(Some(NaiveDate::from_ymd_opt(2022, 12, 31).unwrap()), item.due_date);
+        // Moving the due date re-sorts the store
+        assert_eq!(id, store.list_all_todos()[0].id);
+    }
+
+    #[test]
+    fn edit_single_field() {
+        let mut store = new_test_store("edit_single_field");
+        let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+
+        let item = store
+            .edit(
+                id,
+                ItemChanges {
+                    due_date: Some(Some(NaiveDate::from_ymd_opt(2023, 3, 3).unwrap())),
+                    ..ItemChanges::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!("Todo", item.title);
+        assert_eq!(Some(NaiveDate::from_ymd_opt(2023, 3, 3).unwrap()), item.due_date);
+    }
+
+    #[test]
+    fn edit_invalid_title() {
+        let mut store = new_test_store("edit_invalid_title");
+        let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+
+        let changes = ItemChanges {
+            title: Some(String::new()),
+            ..ItemChanges::default()
+        };
+        assert!(store.edit(id, changes).is_err());
+        assert!(store.edit(id, ItemChanges::default()).is_err());
+        assert_eq!("Todo", store.get_item(id).unwrap().title);
+    }
+
+    #[test]
+    fn configured_max_title_length() {
+        let mut store = new_test_store("configured_max_title_length");
+        let due_date = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+        let long_title = "ü".repeat(60);
+
+        let error = store.add(due_date, &long_title).err().unwrap();
+        assert_eq!("Title must be at most 50 characters long.", &error.message);
+
+        store.set_max_title_length(80);
+        let id = store.add(due_date, &long_title).unwrap().id;
+
+        store.set_max_title_length(10);
+        let changes = ItemChanges {
+            title: Some(String::from("Much too long now")),
+            ..ItemChanges::default()
+        };
+        assert!(store.edit(id, changes).is_err());
+        assert_eq!(long_title, store.get_item(id).unwrap().title);
+    }
+
+    #[test]
+    fn edit_replayed_from_journal() {
+        let filepath = test_filepath("edit_replayed_from_journal");
+        {
+            let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+            let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+            let changes = ItemChanges {
+                title: Some(String::from("Edited")),
+                ..ItemChanges::default()
+            };
+            store.edit(id, changes).unwrap();
+        }
+
+        let store = TodoStore::new_from_persistence(&filepath).unwrap();
+
+        assert_eq!("Edited", store.get_item(0).unwrap().title);
+    }
+
+    #[test]
+    fn completion_times_replayed_and_undone() {
+        let filepath = test_filepath("completion_times_replayed_and_undone");
+        let completed_at = {
+            let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+            let first = store
+                .add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "First")
+                .unwrap()
+                .id;
+            let second = store
+                .add(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(), "Second")
+                .unwrap()
+                .id;
+            store.complete(second).unwrap();
+            store.complete(first).unwrap();
+            store.get_item(first).unwrap().completed_at
+        };
+        assert!(completed_at.is_some());
+
+        let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+        assert_eq!(completed_at, store.get_item(0).unwrap().completed_at);
+        let history: Vec<usize> = store.list_history().iter().map(|item| item.id).collect();
+        assert_eq!(vec![0, 1], history);
+
+        let updated_at = store.get_item(1).unwrap().updated_at;
+        store.reopen(1).unwrap();
+        assert_eq!(None, store.get_item(1).unwrap().completed_at);
+        store.undo().unwrap();
+        assert!(store.get_item(1).unwrap().complete);
+        assert_eq!(updated_at, store.get_item(1).unwrap().updated_at);
+    }
+
+    #[test]
+    fn search_with_query() {
+        let mut store = new_test_store("search_with_query");
+        for input in [
+            "2023-01-10 Deploy api #ops !low",
+            "2023-01-20 Deploy web #ops !high",
+            "2023-03-01 Deploy docs #ops",
+            "2023-01-05 Water plants",
+            "Someday deploy",
+        ] {
+            store.create(TodoItem::parse_input(input).unwrap()).unwrap();
+        }
+        store.complete(3).unwrap();
+
+        let ids = |query: &str| -> Vec<usize> {
+            let query = Query::parse(query).unwrap();
+            store.search(&query).iter().map(|item| item.id).collect()
+        };
+
+        assert_eq!(vec![1, 0], ids(r#"status:open due<2023-02-01 tag:ops title~"deploy" sort:-due"#));
+        assert_eq!(vec![1, 0, 2, 4], ids("deploy sort:priority"));
+        assert_eq!(vec![4], ids("deploy -tag:ops"));
+        assert_eq!(vec![2], ids("due>=2023-02-01"));
+        assert_eq!(vec![4], ids("due:none"));
+        assert_eq!(vec![3], ids("status:done"));
+    }
+
+    #[test]
+    fn find_ranks_fuzzy_matches() {
+        let mut store = new_test_store("find_ranks_fuzzy_matches");
+        for title in ["Apply patch in", "Deploy api", "Water plants", "Call API vendor"] {
+            store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), title).unwrap();
+        }
+        store.complete(1).unwrap();
+
+        let found = store.find("API");
+        let ids: Vec<usize> = found.iter().map(|(item, _)| item.id).collect();
+        assert_eq!(vec![3, 1, 0], ids);
+        assert_eq!(vec![5, 6, 7], found[0].1.positions);
+        assert!(store.find("xyz").is_empty());
+    }
+
+    #[test]
+    fn delete_and_restore() {
+        let mut store = new_test_store("delete_and_restore");
+        let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+
+        store.delete(id).unwrap();
+        assert!(store.list_all_todos().is_empty());
+        assert_eq!(id, store.list_trash()[0].id);
+        assert!(store.complete(id).is_err());
+
+        store.restore(id).unwrap();
+        assert_eq!(id, store.list_all_todos()[0].id);
+        assert!(store.list_trash().is_empty());
+    }
+
+    #[test]
+    fn restore_requires_trashed_item() {
+        let mut store = new_test_store("restore_requires_trashed_item");
+        let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+
+        let error = store.restore(id).err().unwrap();
+
+        assert_eq!("Please select the ID of an item in the trash.", &error.message);
+    }
+
+    #[test]
+    fn empty_trash_persisted() {
+        let filepath = test_filepath("empty_trash_persisted");
+        {
+            let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+            let deleted = store
+                .add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Deleted")
+                .unwrap()
+                .id;
+            let trashed = store
+                .add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Trashed")
+                .unwrap()
+                .id;
+            store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Kept").unwrap();
+            store.delete(deleted).unwrap();
+            assert_eq!(1, store.empty_trash().unwrap());
+            store.delete(trashed).unwrap();
+            store.compact().unwrap();
+        }
+
+        let store = TodoStore::new_from_persistence(&filepath).unwrap();
+
+        assert_eq!(1, store.list_all_todos().len());
+        assert_eq!("Trashed", store.list_trash()[0].title);
+    }
+
+    #[test]
+    fn undo_redo_every_action() {
+        let mut store = new_test_store("undo_redo_every_action");
+        let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+        store.complete(id).unwrap();
+        let changes = ItemChanges {
+            title: Some(String::from("Edited")),
+            ..ItemChanges::default()
+        };
+        store.edit(id, changes).unwrap();
+        store.reopen(id).unwrap();
+        store.delete(id).unwrap();
+        store.empty_trash().unwrap();
+        assert!(store.list_trash().is_empty());
+
+        store.undo().unwrap();
+        assert_eq!(id, store.list_trash()[0].id);
+        store.undo().unwrap();
+        assert_eq!(id, store.list_all_todos()[0].id);
+        store.undo().unwrap();
+        assert!(store.get_item(id).unwrap().complete);
+        store.undo().unwrap();
+        assert_eq!("Todo", store.get_item(id).unwrap().title);
+        store.undo().unwrap();
+        assert!(!store.get_item(id).unwrap().complete);
+        store.undo().unwrap();
+        assert!(store.get_item(id).is_err());
+        assert_eq!("Nothing to undo.", store.undo().err().unwrap().message);
+
+        for _ in 0..6 {
+            store.redo().unwrap();
+        }
+        assert!(store.list_all_todos().is_empty());
+        assert!(store.list_trash().is_empty());
+        assert_eq!("Nothing to redo.", store.redo().err().unwrap().message);
+    }
+
+    #[test]
+    fn new_change_clears_redo() {
+        let mut store = new_test_store("new_change_clears_redo");
+        let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+        store.complete(id).unwrap();
+        store.undo().unwrap();
+
+        let changes = ItemChanges {
+            title: Some(String::from("Edited")),
+            ..ItemChanges::default()
+        };
+        store.edit(id, changes).unwrap();
+
+        assert!(store.redo().is_err());
+    }
+
+    #[test]
+    fn undo_history_survives_restart() {
+        let filepath = test_filepath("undo_history_survives_restart");
+        {
+            let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+            let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+            store.complete(id).unwrap();
+            let changes = ItemChanges {
+                title: Some(String::from("Edited")),
+                ..ItemChanges::default()
+            };
+            store.edit(id, changes).unwrap();
+            store.compact().unwrap();
+            // The undo after compaction is only in the journal
+            store.undo().unwrap();
+        }
+
+        let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+        assert_eq!("Todo", store.get_item(0).unwrap().title);
+
+        store.undo().unwrap();
+        assert!(!store.get_item(0).unwrap().complete);
+        store.redo().unwrap();
+        store.redo().unwrap();
+        assert_eq!("Edited", store.get_item(0).unwrap().title);
+    }
+
+    #[test]
+    fn complete_invalid_id() {
+        let mut store = new_test_store("complete_invalid_id");
+
+        let error = store.complete(42).err().unwrap();
+
+        assert_eq!("Please select a valid ID.", &error.message);
+    }
+
+    #[test]
+    fn journal_replayed_after_crash() {
+        let filepath = test_filepath("journal_replayed_after_crash");
+        {
+            // Dropping the store without compacting simulates the process being killed
+            let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+            let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+            store.complete(id).unwrap();
+        }
+        assert!(fs::metadata(format!("{}.journal", filepath)).is_ok());
+
+        let store = TodoStore::new_from_persistence(&filepath).unwrap();
+
+        assert_eq!(1, store.list_history().len());
+        assert!(fs::metadata(format!("{}.journal", filepath)).is_err());
+    }
+
+    #[test]
+    fn journal_torn_tail_ignored() {
+        let filepath = test_filepath("journal_torn_tail_ignored");
+        {
+            let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+            store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap();
+        }
+        let mut journal = fs::read_to_string(format!("{}.journal", filepath)).unwrap();
+        journal.push_str(r#"{"seq":2,"action":"comp"#);
+        fs::write(format!("{}.journal", filepath), journal).unwrap();
+
+        let store = TodoStore::new_from_persistence(&filepath).unwrap();
+
+        assert_eq!(1, store.list_incomplete_todos().len());
+    }
+
+    #[test]
+    fn journal_already_compacted_not_replayed_twice() {
+        let filepath = test_filepath("journal_already_compacted_not_replayed_twice");
+        let journal;
+        {
+            let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+            store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap();
+            journal = fs::read_to_string(format!("{}.journal", filepath)).unwrap();
+            store.compact().unwrap();
+        }
+        // Simulate a crash between writing the snapshot and removing the journal
+        fs::write(format!("{}.journal", filepath), journal).unwrap();
+
+        let store = TodoStore::new_from_persistence(&filepath).unwrap();
+
+        assert_eq!(1, store.list_all_todos().len());
+    }
+
+    #[test]
+    fn second_instance_fails_while_locked() {
+        let filepath = test_filepath("second_instance_fails_while_locked");
+        let _store = TodoStore::new_from_persistence(&filepath).unwrap();
+
+        let error = TodoStore::new_from_persistence(&filepath).err().unwrap();
+
+        assert_eq!(
+            format!("{} is in use by another todo_assistant process.", filepath),
+            error.message
+        );
+    }
+
+    #[test]
+    fn empty_file_loads_as_empty_store() {
+        let filepath = test_filepath("empty_file_loads_as_empty_store");
+        fs::write(&filepath, "").unwrap();
+
+        let store = TodoStore::new_from_persistence(&filepath).unwrap();
+
+        assert!(store.list_all_todos().is_empty());
+    }
+
+    #[test]
+    fn current_file_loads_without_migrating() {
+        let filepath = test_filepath("current_file_loads_without_migrating");
+        let contents = fs::read_to_string(&filepath).unwrap();
+
+        TodoStore::new_from_persistence(&filepath).unwrap();
+
+        assert_eq!(contents, fs::read_to_string(&filepath).unwrap());
+    }
+
+    #[test]
+    fn legacy_file_migrated_with_backup() {
+        let filepath = test_filepath("legacy_file_migrated_with_backup");
+        let legacy_contents = r#"{"store": [
+            {"title": "Todo item one", "due_date": "2023-01-01", "complete": true},
+            {"title": "Todo item two", "due_date": "2023-02-02", "complete": false}
+        ]}"#;
+        fs::write(&filepath, legacy_contents).unwrap();
+
+        let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+
+        assert!(store.get_item(0).unwrap().complete);
+        assert_eq!("Todo item two", store.get_item(1).unwrap().title);
+        assert_eq!(legacy_contents, fs::read_to_string(format!("{}.v0.bak", filepath)).unwrap());
+        assert!(fs::read_to_string(&filepath).unwrap().contains("\"schema_version\": 6"));
+        assert_eq!(2, store.add(NaiveDate::from_ymd_opt(2023, 3, 3).unwrap(), "Three").unwrap().id);
+    }
+
+    // Loads an empty store and fails every write, or every snapshot, once told to
+    struct FailingBackend {
+        failing: Rc<Cell<bool>>,
+        failing_snapshots: Rc<Cell<bool>>,
+        pending_changes: Rc<Cell<usize>>,
+    }
+
+    impl StorageBackend for FailingBackend {
+        fn load(&mut self) -> Result<LoadedStore, TodoError> {
+            Ok(LoadedStore {
+                snapshot: StoreSnapshot {
+                    items: Vec::new(),
+                    trash: Vec::new(),
+                    projects: Vec::new(),
+                    views: Vec::new(),
+                    next_id: 0,
+                    history: UndoHistory::default(),
+                },
+                pending_actions: Vec::new(),
+                needs_snapshot: false,
+            })
+        }
+
+        fn apply_change(&mut self, _: &StoreChange) -> Result<(), TodoError> {
+            if self.failing.get() {
+                return Err(TodoError::new_from_msg(String::from("Disk full.")));
+            }
+            self.pending_changes.set(self.pending_changes.get() + 1);
+
+            Ok(())
+        }
+
+        fn snapshot(&mut self, _: &StoreSnapshot) -> Result<(), TodoError> {
+            if self.failing_snapshots.get() {
+                return Err(TodoError::new_from_msg(String::from("Disk full.")));
+            }
+            self.pending_changes.set(0);
+
+            Ok(())
+        }
+
+        fn pending_change_count(&self) -> usize {
+            self.pending_changes.get()
+        }
+    }
+
+    #[test]
+    fn failed_write_leaves_store_unchanged() {
+        let failing = Rc::new(Cell::new(false));
+        let backend = FailingBackend {
+            failing: Rc::clone(&failing),
+            failing_snapshots: Rc::new(Cell::new(false)),
+            pending_changes: Rc::new(Cell::new(0)),
+        };
+        let mut store = TodoStore::new_from_backend(Box::new(backend)).unwrap();
+        let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+
+        failing.set(true);
+        assert_eq!("Disk full.", store.complete(id).err().unwrap().message);
+        assert!(store.add(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(), "Other").is_err());
+        assert!(store.undo().is_err());
+
+        failing.set(false);
+        assert!(!store.get_item(id).unwrap().complete);
+        assert_eq!(1, store.list_all_todos().len());
+        // The failed changes never reached the undo history either
+        assert_eq!("create item 0", store.undo().unwrap().to_string());
+        assert_eq!(1, store.add(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(), "Other").unwrap().id);
+    }
+
+    #[test]
+    fn failed_snapshot_keeps_the_change() {
+        let failing_snapshots = Rc::new(Cell::new(true));
+        let pending_changes = Rc::new(Cell::new(0));
+        let backend = FailingBackend {
+            failing: Rc::new(Cell::new(false)),
+            failing_snapshots: Rc::clone(&failing_snapshots),
+            pending_changes: Rc::clone(&pending_changes),
+        };
+        let mut store = TodoStore::new_from_backend(Box::new(backend)).unwrap();
+        let due_date = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+
+        // Every commit from the 50th on tries to snapshot and fails
+        for i in 0..51 {
+            store.add(due_date, &format!("Todo {}", i)).unwrap();
+        }
+        assert_eq!(51, store.list_all_todos().len());
+        assert_eq!(51, pending_changes.get());
+
+        failing_snapshots.set(false);
+        store.add(due_date, "Last").unwrap();
+        assert_eq!(0, pending_changes.get());
+    }
+
+    #[test]
+    fn legacy_edits_replayed_and_undone() {
+        let filepath = test_filepath("legacy_edits_replayed_and_undone");
+        fs::write(&filepath, r#"{"schema_version": 5, "store": [
+            {"id": 0, "title": "Renamed", "due_date": "2023-01-02", "complete": false},
+            {"id": 1, "title": "Two", "due_date": "2023-02-02", "complete": false}
+        ], "trash": [], "projects": [], "next_id": 2, "history": {"undo": [
+            {"action": {"action": "edit", "id": 0, "title": "Renamed", "due_date": "2023-01-02"},
+             "inverse": {"action": "edit", "id": 0, "title": "One", "due_date": "2023-01-01"}}
+        ], "redo": []}}"#).unwrap();
+        // Edits written before items had more fields, the last one as the final journal line
+        fs::write(
+            format!("{}.journal", filepath),
+            concat!(
+                r#"{"seq":1,"action":"edit","id":1,"title":"Second","due_date":"2023-02-03"}"#,
+                "\n",
+                r#"{"seq":2,"action":"edit","id":0,"title":"First","due_date":"2023-01-03"}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+
+        assert_eq!("First", store.get_item(0).unwrap().title);
+        assert_eq!(
+            Some(NaiveDate::from_ymd_opt(2023, 1, 3).unwrap()),
+            store.get_item(0).unwrap().due_date
+        );
+        assert_eq!("Second", store.get_item(1).unwrap().title);
+
+        // Replayed edits are undone first, then the one from the snapshot history
+        store.undo().unwrap();
+        assert_eq!("Renamed", store.get_item(0).unwrap().title);
+        store.undo().unwrap();
+        assert_eq!("Two", store.get_item(1).unwrap().title);
+        store.undo().unwrap();
+        assert_eq!("One", store.get_item(0).unwrap().title);
+        assert_eq!(
+            Some(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap()),
+            store.get_item(0).unwrap().due_date
+        );
+        store.redo().unwrap();
+        assert_eq!("Renamed", store.get_item(0).unwrap().title);
+        drop(store);
+
+        // The history is written back and read again
+        let store = TodoStore::new_from_persistence(&filepath).unwrap();
+        assert_eq!("Renamed", store.get_item(0).unwrap().title);
+    }
+
+    #[test]
+    fn sqlite_backend_round_trip() {
+        let filepath = env::temp_dir().join("todo_assistant_sqlite_backend_round_trip.sqlite");
+        let filepath = filepath.to_str().unwrap();
+        let _ = fs::remove_file(filepath);
+        {
+            let backend = SqliteBackend::open(filepath).unwrap();
+            let mut store = TodoStore::new_from_backend(Box::new(backend)).unwrap();
+            store.add(NaiveDate::from_ymd_opt(2023, 2, 2).unwrap(), "Later").unwrap();
+            let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Sooner").unwrap().id;
+            store.complete(id).unwrap();
+        }
+
+        let backend = SqliteBackend::open(filepath).unwrap();
+        let mut store = TodoStore::new_from_backend(Box::new(backend)).unwrap();
+
+        let titles: Vec<&str> = store
+            .list_all_todos()
+            .iter()
+            .map(|item| item.title.as_str())
+            .collect();
+        assert_eq!(vec!["Sooner", "Later"], titles);
+        assert!(store.get_item(1).unwrap().complete);
+        assert_eq!(2, store.add(NaiveDate::from_ymd_opt(2023, 3, 3).unwrap(), "Next").unwrap().id);
+
+        // Trashed items are kept apart and removed rows stay removed
+        store.delete(0).unwrap();
+        store.delete(1).unwrap();
+        store.restore(1).unwrap();
+        store.empty_trash().unwrap();
+        store.undo().unwrap();
+        store.redo().unwrap();
+        drop(store);
+
+        let backend = SqliteBackend::open(filepath).unwrap();
+        let store = TodoStore::new_from_backend(Box::new(backend)).unwrap();
+        assert_eq!(2, store.list_all_todos().len());
+        assert!(store.get_item(0).is_err());
+        assert!(store.list_trash().is_empty());
+    }
+
+    #[test]
+    fn sqlite_projects_survive_restart() {
+        let filepath = env::temp_dir().join("todo_assistant_sqlite_projects.sqlite");
+        let filepath = filepath.to_str().unwrap();
+        let _ = fs::remove_file(filepath);
+        {
+            let backend = SqliteBackend::open(filepath).unwrap();
+            let mut store = TodoStore::new_from_backend(Box::new(backend)).unwrap();
+            store.create_project("Work").unwrap();
+            store
+                .create(TodoItem::parse_input("2023-01-01 Report @work").unwrap())
+                .unwrap();
+            store.rename_project("work", "Office").unwrap();
+        }
+
+        let backend = SqliteBackend::open(filepath).unwrap();
+        let store = TodoStore::new_from_backend(Box::new(backend)).unwrap();
+
+        assert_eq!(1, store.list_project("office").unwrap().len());
+        assert_eq!(Some(String::from("Office")), store.get_item(0).unwrap().project);
+    }
+
+    #[test]
+    fn saved_views_lifecycle() {
+        let filepath = test_filepath("saved_views_lifecycle");
+        {
+            let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+            store.create(TodoItem::parse_input("2023-01-01 Rotate keys #ops").unwrap()).unwrap();
+            store.add(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(), "Groceries").unwrap();
+
+            let view = SavedView::new("ops", "tag:ops", Vec::new()).unwrap();
+            store.save_view(view).unwrap();
+            let view =
+                SavedView::new("open", "status:open sort:-due", vec![Column::Id, Column::Title])
+                    .unwrap();
+            store.save_view(view).unwrap();
+        }
+
+        let mut store = TodoStore::new_from_persistence(&filepath).unwrap();
+        let names: Vec<&str> = store.list_views().iter().map(|view| view.name.as_str()).collect();
+        assert_eq!(vec!["ops", "open"], names);
+        assert_eq!(vec![Column::Id, Column::Title], store.get_view("OPEN").unwrap().columns);
+
+        // Saving under an existing name replaces the view, and undo brings the old one back
+        store.save_view(SavedView::new("ops", "tag:ops status:done", Vec::new()).unwrap()).unwrap();
+        assert_eq!(2, store.list_views().len());
+        assert_eq!("tag:ops status:done", store.get_view("ops").unwrap().query);
+        store.undo().unwrap();
+        assert_eq!("tag:ops", store.get_view("ops").unwrap().query);
+
+        store.delete_view("ops").unwrap();
+        assert!(store.get_view("ops").is_err());
+        assert!(store.delete_view("ops").is_err());
+        store.undo().unwrap();
+        assert_eq!("tag:ops", store.get_view("ops").unwrap().query);
+    }
+
+    #[test]
+    fn sqlite_saved_views_survive_restart() {
+        let filepath = env::temp_dir().join("todo_assistant_sqlite_views.sqlite");
+        let filepath = filepath.to_str().unwrap();
+        let _ = fs::remove_file(filepath);
+        {
+            let backend = SqliteBackend::open(filepath).unwrap();
+            let mut store = TodoStore::new_from_backend(Box::new(backend)).unwrap();
+            store.save_view(SavedView::new("this-week", "due<=+7d", Vec::new()).unwrap()).unwrap();
+        }
+
+        let backend = SqliteBackend::open(filepath).unwrap();
+        let store = TodoStore::new_from_backend(Box::new(backend)).unwrap();
+
+        assert_eq!("due<=+7d", store.get_view("this-week").unwrap().query);
+    }
+
+    #[test]
+    fn sqlite_keeps_undated_items() {
+        let filepath = env::temp_dir().join("todo_assistant_sqlite_undated.sqlite");
+        let filepath = filepath.to_str().unwrap();
+        let _ = fs::remove_file(filepath);
+        {
+            let backend = SqliteBackend::open(filepath).unwrap();
+            let mut store = TodoStore::new_from_backend(Box::new(backend)).unwrap();
+            store.create(TodoItem::parse_input("Learn Rust").unwrap()).unwrap();
+        }
+
+        let backend = SqliteBackend::open(filepath).unwrap();
+        let store = TodoStore::new_from_backend(Box::new(backend)).unwrap();
+
+        assert_eq!(None, store.get_item(0).unwrap().due_date);
+    }
+
+    #[test]
+    fn sqlite_undo_history_survives_restart() {
+        let filepath = env::temp_dir().join("todo_assistant_sqlite_undo_history.sqlite");
+        let filepath = filepath.to_str().unwrap();
+        let _ = fs::remove_file(filepath);
+        {
+            let backend = SqliteBackend::open(filepath).unwrap();
+            let mut store = TodoStore::new_from_backend(Box::new(backend)).unwrap();
+            let id = store.add(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), "Todo").unwrap().id;
+            store.delete(id).unwrap();
+        }
+
+        let backend = SqliteBackend::open(filepath).unwrap();
+        let mut store = TodoStore::new_from_backend(Box::new(backend)).unwrap();
+        store.undo().unwrap();
 
-        println!("{}", table_row);
+        assert_eq!(0, store.list_all_todos()[0].id);
     }
 }
@@ -0,0 +1,179 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::err::TodoError;
+use crate::todo::Query;
+
+const VIEW_NAME_PATTERN: &str = r"[A-Za-z0-9-_]{1,30}";
+
+// Words that follow 'view' on the command line, so they cannot name a view
+const RESERVED_VIEW_NAMES: [&str; 2] = ["save", "delete"];
+
+// A named query, run with `todo view NAME` or from the menu. The query is kept as typed so
+// relative due dates such as "due<=+7d" are resolved each time the view is shown.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedView {
+    pub name: String,
+    pub query: String,
+    // Columns to print, in order. Empty shows the regular columns.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub columns: Vec<Column>,
+}
+
+// A column of the item table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Column {
+    Id,
+    Done,
+    Due,
+    Priority,
+    Title,
+    Repeats,
+    BlockedBy,
+    Tags,
+    Project,
+    Created,
+    Completed,
+    Updated,
+}
+
+const COLUMNS: [(Column, &str); 12] = [
+    (Column::Id, "id"),
+    (Column::Done, "done"),
+    (Column::Due, "due"),
+    (Column::Priority, "priority"),
+    (Column::Title, "title"),
+    (Column::Repeats, "repeats"),
+    (Column::BlockedBy, "blocked-by"),
+    (Column::Tags, "tags"),
+    (Column::Project, "project"),
+    (Column::Created, "created"),
+    (Column::Completed, "completed"),
+    (Column::Updated, "updated"),
+];
+
+impl SavedView {
+    // Checks the query and columns up front so a saved view always runs
+    pub fn new(name: &str, query: &str, columns: Vec<Column>) -> Result<SavedView, TodoError> {
+        Query::parse(query)?;
+
+        Ok(SavedView {
+            name: SavedView::parse_name(name)?,
+            query: String::from(query.trim()),
+            columns,
+        })
+    }
+
+    pub fn parse_name(input: &str) -> Result<String, TodoError> {
+        let name = input.trim();
+        let regex = Regex::new(&format!("^{}$", VIEW_NAME_PATTERN)).unwrap();
+
+        if !regex.is_match(name) {
+            return Err(TodoError::new_from_msg(format!(
+                "Invalid view name '{}'. View names must be 1-30 letters, numbers, - or _.",
+                name
+            )));
+        }
+        if RESERVED_VIEW_NAMES.contains(&name.to_lowercase().as_str()) {
+            return Err(TodoError::new_from_msg(format!(
+                "'{}' cannot be used as a view name.",
+                name
+            )));
+        }
+
+        Ok(String::from(name))
+    }
+
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name.trim())
+    }
+}
+
+impl fmt::Display for SavedView {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.query)?;
+        if !self.columns.is_empty() {
+            let columns: Vec<&str> = self.columns.iter().map(Column::as_str).collect();
+            write!(f, " (columns {})", columns.join(","))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Column {
+    pub fn parse(input: &str) -> Result<Column, TodoError> {
+        let input = input.trim().to_lowercase();
+
+        COLUMNS
+            .iter()
+            .find(|(_, name)| *name == input)
+            .map(|(column, _)| *column)
+            .ok_or(TodoError::new_from_msg(format!(
+                "Unknown column '{}'. Expected {}.",
+                input,
+                COLUMNS
+                    .iter()
+                    .map(|(_, name)| *name)
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            )))
+    }
+
+    // Parses a comma separated list such as "id,due,title"
+    pub fn parse_list(input: &str) -> Result<Vec<Column>, TodoError> {
+        let mut columns = Vec::new();
+        for column in input.split(',').filter(|column| !column.trim().is_empty()) {
+            let column = Column::parse(column)?;
+            if !columns.contains(&column) {
+                columns.push(column);
+            }
+        }
+
+        Ok(columns)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        COLUMNS
+            .iter()
+            .find(|(column, _)| column == self)
+            .map(|(_, name)| *name)
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod saved_view_tests {
+    use super::*;
+
+    #[test]
+    fn new_view() {
+        let view = SavedView::new(
+            "this-week",
+            " status:open due<=+7d sort:due ",
+            Column::parse_list("id,due,title,created").unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!("status:open due<=+7d sort:due", view.query);
+        assert_eq!(
+            "this-week: status:open due<=+7d sort:due (columns id,due,title,created)",
+            view.to_string()
+        );
+        assert!(view.is_named("This-Week"));
+    }
+
+    #[test]
+    fn bad_views() {
+        let error = SavedView::new("this week", "status:open", Vec::new()).err().unwrap();
+        assert_eq!(
+            "Invalid view name 'this week'. View names must be 1-30 letters, numbers, - or _.",
+            &error.message
+        );
+        assert!(SavedView::new("save", "status:open", Vec::new()).is_err());
+        assert!(SavedView::new("ops", "stauts:open", Vec::new()).is_err());
+        assert!(Column::parse_list("id,size").is_err());
+    }
+}